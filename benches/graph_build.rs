@@ -0,0 +1,53 @@
+//! Builds and tears down a graph of mapped streams, once with every step in
+//! its own transaction and once inside a single transaction, where cycle
+//! collection is deferred until the transaction ends.
+//!
+//! Run with `cargo bench --bench graph_build`.
+
+extern crate sodium_rust;
+
+use sodium_rust::sodium::IsStream;
+use sodium_rust::sodium::SodiumCtx;
+use sodium_rust::sodium::StreamSink;
+use std::time::Duration;
+use std::time::Instant;
+
+const STAGES: usize = 200;
+const RUNS: usize = 20;
+
+fn build(s: &StreamSink<i32>) {
+    let mut listeners = Vec::with_capacity(STAGES);
+    let mut sa = s.map(|a: &i32| *a);
+    for i in 0..STAGES {
+        let i = i as i32;
+        sa = sa.map(move |a: &i32| *a + i).filter(|a: &i32| *a % 2 == 0);
+        listeners.push(sa.listen(|_: &i32| {}));
+    }
+    for l in listeners {
+        l.unlisten();
+    }
+}
+
+fn run<F: Fn(&SodiumCtx, &StreamSink<i32>)>(name: &str, f: F) {
+    let mut total = Duration::from_secs(0);
+    let mut collections = 0;
+    for _ in 0..RUNS {
+        let sodium_ctx = SodiumCtx::new();
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let start = Instant::now();
+        f(&sodium_ctx, &s);
+        total += start.elapsed();
+        collections += sodium_ctx.gc_ctx().stats().collections;
+    }
+    println!(
+        "{:<24} {:>10.3} ms/run {:>8} collections/run",
+        name,
+        total.as_secs_f64() * 1000.0 / RUNS as f64,
+        collections / RUNS as u64
+    );
+}
+
+fn main() {
+    run("per-step transactions", |_, s| build(s));
+    run("single transaction", |sodium_ctx, s| sodium_ctx.transaction(|_| build(s)));
+}
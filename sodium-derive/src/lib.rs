@@ -0,0 +1,150 @@
+//! `#[derive(Trace, Finalize)]` for sodium-rust.
+//!
+//! Both derives visit every field of a struct or enum. A field marked
+//! `#[trace(skip)]` is left out, which is only safe when it can never hold a
+//! sodium object. Every type parameter gets both a `Trace` and a `Finalize`
+//! bound, since sodium's own types require both of their parameters.
+//!
+//! Enable the `derive` feature of sodium-rust and import the derives from
+//! `sodium_rust::sodium::gc`.
+
+extern crate proc_macro;
+
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use quote::quote;
+use syn::parse_macro_input;
+use syn::parse_quote;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Error;
+use syn::Field;
+use syn::Fields;
+use syn::Generics;
+use syn::Path;
+
+#[proc_macro_derive(Trace, attributes(trace))]
+pub fn derive_trace(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let trait_path: Path = parse_quote!(::sodium_rust::sodium::gc::Trace);
+    let result = visit_fields(&input, false, |field| quote! {
+        ::sodium_rust::sodium::gc::Trace::trace(#field, __tracer);
+    }).map(|body| {
+        let name = &input.ident;
+        let generics = add_bounds(&input.generics);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        quote! {
+            impl #impl_generics #trait_path for #name #ty_generics #where_clause {
+                #[allow(unused_variables)]
+                fn trace(&self, __tracer: &mut dyn FnMut(&::sodium_rust::sodium::gc::GcDep)) {
+                    #body
+                }
+            }
+        }
+    });
+    into_output(result)
+}
+
+#[proc_macro_derive(Finalize, attributes(trace))]
+pub fn derive_finalize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let trait_path: Path = parse_quote!(::sodium_rust::sodium::gc::Finalize);
+    let result = visit_fields(&input, true, |field| quote! {
+        ::sodium_rust::sodium::gc::Finalize::finalize(#field);
+    }).map(|body| {
+        let name = &input.ident;
+        let generics = add_bounds(&input.generics);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        quote! {
+            impl #impl_generics #trait_path for #name #ty_generics #where_clause {
+                fn finalize(&mut self) {
+                    #body
+                }
+            }
+        }
+    });
+    into_output(result)
+}
+
+fn into_output(result: syn::Result<TokenStream>) -> proc_macro::TokenStream {
+    match result {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into()
+    }
+}
+
+fn add_bounds(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::sodium_rust::sodium::gc::Trace));
+        param.bounds.push(parse_quote!(::sodium_rust::sodium::gc::Finalize));
+    }
+    generics
+}
+
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("trace") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
+
+/// Destructures `self` and emits `visit(binding)` for every field that is
+/// not skipped. Each binding is a `&T`, or a `&mut T` when `mutable` is set.
+fn visit_fields<F: Fn(&TokenStream) -> TokenStream>(input: &DeriveInput, mutable: bool, visit: F) -> syn::Result<TokenStream> {
+    let ref_kw = if mutable { quote!(ref mut) } else { quote!(ref) };
+    let visit_variant = |path: TokenStream, fields: &Fields| -> syn::Result<TokenStream> {
+        let mut bindings = Vec::new();
+        let mut body = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            let binding = format_ident!("__field{}", i);
+            let skipped = is_skipped(field)?;
+            let pattern = if skipped { quote!(_) } else { quote!(#ref_kw #binding) };
+            bindings.push(match field.ident {
+                Some(ref ident) => quote!(#ident: #pattern),
+                None => pattern
+            });
+            if !skipped {
+                body.push(visit(&quote!(#binding)));
+            }
+        }
+        let pattern = match *fields {
+            Fields::Named(_) => quote!(#path { #(#bindings),* }),
+            Fields::Unnamed(_) => quote!(#path ( #(#bindings),* )),
+            Fields::Unit => quote!(#path)
+        };
+        Ok(quote!(#pattern => { #(#body)* }))
+    };
+    let arms = match input.data {
+        Data::Struct(ref data) => vec![visit_variant(quote!(Self), &data.fields)?],
+        Data::Enum(ref data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                arms.push(visit_variant(quote!(Self::#ident), &variant.fields)?);
+            }
+            arms
+        },
+        Data::Union(_) => return Err(Error::new_spanned(&input.ident, "Trace and Finalize can not be derived for unions"))
+    };
+    if arms.is_empty() {
+        return Ok(quote!());
+    }
+    Ok(quote! {
+        #[allow(unreachable_patterns)]
+        match *self {
+            #(#arms)*
+        }
+    })
+}
@@ -1,4 +1,19 @@
+#[cfg(feature = "futures")]
+extern crate futures;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "derive")]
+extern crate sodium_derive;
+
+// Lets the derives' `::sodium_rust::...` paths resolve inside this crate's tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as sodium_rust;
+
 pub mod sodium;
 
+#[cfg(any(feature = "record", feature = "persist", all(test, feature = "serde")))]
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+
 #[cfg(test)]
 mod tests;
@@ -8,6 +8,8 @@ use sodium::IsLambda4;
 use sodium::IsLambda5;
 use sodium::IsLambda6;
 use sodium::Listener;
+use sodium::SodiumCtx;
+use sodium::SodiumError;
 use sodium::Stream;
 use sodium::gc::Finalize;
 use sodium::gc::GcDep;
@@ -28,6 +30,13 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
         self.impl_.sample()
     }
 
+    /// Like `sample`, but returns `SodiumError::SampledBeforeLooped` instead
+    /// of panicking when the cell depends on a `CellLoop` that is not looped
+    /// yet.
+    pub fn try_sample(&self) -> Result<A,SodiumError> {
+        self.impl_.try_sample()
+    }
+
     pub fn map<B: Clone + Trace + Finalize + 'static,F:IsLambda1<A,B> + 'static>(
         &self,
         f: F
@@ -73,6 +82,30 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
         }
     }
 
+    /// A cell holding the values of all of `cells`, updated by a single node
+    /// however many cells there are.
+    pub fn lift_all<CA:IsCell<A>>(sodium_ctx: &SodiumCtx, cells: Vec<CA>) -> Cell<Vec<A>> {
+        let cells: Vec<impl_::Cell<A>> = cells.iter().map(|c| c.to_cell().impl_).collect();
+        if cells.is_empty() {
+            return sodium_ctx.new_cell(Vec::new());
+        }
+        Cell {
+            impl_: impl_::Cell::lift_all(&sodium_ctx.impl_, cells)
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn _lift_n<INIT,NEXT,THUNK>(node_deps: Vec<impl_::Node>, update_deps: Vec<Dep>, init: INIT, next: NEXT) -> Cell<A>
+        where INIT: Fn()->A + 'static,
+              NEXT: Fn()->THUNK + 'static,
+              THUNK: Fn()->A + 'static
+    {
+        let sodium_ctx = node_deps[0].sodium_ctx();
+        Cell {
+            impl_: impl_::Cell::lift_n(&sodium_ctx, node_deps, update_deps, init, next, "lift!")
+        }
+    }
+
     pub fn switch_s<SA:IsStream<A> + Trace + Finalize + Clone + 'static,CSA:IsCell<SA>>(csa: CSA) -> Stream<A> {
         Stream {
             impl_: impl_::Cell::switch_s(csa.to_cell().impl_.map(|sa:&SA| sa.to_stream().impl_))
@@ -85,6 +118,16 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
         }
     }
 
+    /// Switches to the cell `f` returns for each value, with the same
+    /// semantics as `Cell::switch_c`.
+    pub fn switch_map_c<B,CB,F>(&self, f: F) -> Cell<B>
+        where B: Trace + Finalize + Clone + 'static,
+              CB: IsCell<B> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,CB> + 'static
+    {
+        Cell::switch_c(self.map(f))
+    }
+
     pub fn listen<CALLBACK:FnMut(&A)+'static>(
         &self,
         callback: CALLBACK
@@ -100,6 +143,15 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
     }
 }
 
+impl<A: Clone + Trace + Finalize + PartialEq + 'static> Cell<A> {
+    /// A cell that only updates when its value changes.
+    pub fn calm(&self) -> Cell<A> {
+        Cell {
+            impl_: self.impl_.calm()
+        }
+    }
+}
+
 impl<A: Clone + Trace + Finalize + 'static> Clone for Cell<A> {
     fn clone(&self) -> Self {
         Cell {
@@ -119,3 +171,34 @@ impl<A: Finalize> Finalize for Cell<A> {
         self.impl_.finalize();
     }
 }
+
+/// Lifts a function over any number of cells in a single node, e.g.
+/// `lift!(|a: &i32, b: &i32, c: &i32| a + b + c, ca, cb, cc)`.
+#[macro_export]
+macro_rules! lift {
+    ($f:expr, $($c:expr),+ $(,)*) => {
+        $crate::lift!(@bind $f; []; $($c),+)
+    };
+    // Binds each cell to its own `x`, as macro hygiene keeps them apart.
+    (@bind $f:expr; [$(($x:ident, $c:expr))*]; $head:expr $(, $rest:expr)*) => {
+        $crate::lift!(@bind $f; [$(($x, $c))* (x, $head)]; $($rest),*)
+    };
+    (@bind $f:expr; [$(($x:ident, $c:expr))*]; ) => {{
+        let f = ::std::rc::Rc::new($f);
+        $(let $x = $crate::sodium::IsCell::to_cell(&$c);)*
+        $crate::sodium::Cell::_lift_n(
+            vec![$($x.impl_._node().clone()),*],
+            vec![$($x.to_dep()),*],
+            {
+                let f = f.clone();
+                $(let $x = $x.clone();)*
+                move || (*f)($(&$x.impl_.sample_no_trans()),*)
+            },
+            move || {
+                $(let $x = $x.impl_._next_value_thunk();)*
+                let f = f.clone();
+                move || (*f)($($x.get()),*)
+            }
+        )
+    }};
+}
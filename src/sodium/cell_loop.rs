@@ -1,5 +1,6 @@
 use sodium::Cell;
 use sodium::IsCell;
+use sodium::SodiumError;
 use sodium::gc::Finalize;
 use sodium::gc::Trace;
 use sodium::impl_;
@@ -14,6 +15,14 @@ impl<A: Trace + Finalize + Clone + 'static> CellLoop<A> {
         self.impl_.loop_(ca.to_cell().impl_);
     }
 
+    pub fn try_loop<CA:IsCell<A>>(&self, ca: CA) -> Result<(),SodiumError> {
+        self.impl_.try_loop(ca.to_cell().impl_)
+    }
+
+    pub fn is_looped(&self) -> bool {
+        self.impl_.is_looped()
+    }
+
     pub fn to_cell(&self) -> Cell<A> {
         Cell {
             impl_: self.impl_.to_cell()
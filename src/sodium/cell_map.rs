@@ -0,0 +1,298 @@
+use sodium::Cell;
+use sodium::IsStream;
+use sodium::Operational;
+use sodium::Stream;
+use sodium::gc::Finalize;
+use sodium::gc::GcDep;
+use sodium::gc::Trace;
+use sodium::impl_;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// A keyed collection that publishes what changed in each transaction as
+/// well as its full contents. Operators work on the diffs, so e.g. `map`
+/// only calls its function for the entries that changed.
+pub struct CellMap<K,V> {
+    diffs: Stream<Vec<MapDiff<K,V>>>,
+    snapshot: Cell<BTreeMap<K,V>>
+}
+
+/// A requested change, fed into `CellMap::hold`.
+#[derive(Clone,Debug,PartialEq)]
+pub enum MapChange<K,V> {
+    /// Inserts or replaces the value for the key.
+    Insert(K,V),
+    Remove(K)
+}
+
+/// A change that took effect. `Remove` carries the removed value and
+/// `Update` carries the old value followed by the new one.
+#[derive(Clone,Debug,PartialEq)]
+pub enum MapDiff<K,V> {
+    Insert(K,V),
+    Remove(K,V),
+    Update(K,V,V)
+}
+
+impl<K,V> MapDiff<K,V> {
+    pub fn key(&self) -> &K {
+        match *self {
+            MapDiff::Insert(ref k, _) => k,
+            MapDiff::Remove(ref k, _) => k,
+            MapDiff::Update(ref k, _, _) => k
+        }
+    }
+
+    pub fn map_value<W,F: Fn(&V)->W>(&self, f: F) -> MapDiff<K,W> where K: Clone {
+        match *self {
+            MapDiff::Insert(ref k, ref v) => MapDiff::Insert(k.clone(), f(v)),
+            MapDiff::Remove(ref k, ref v) => MapDiff::Remove(k.clone(), f(v)),
+            MapDiff::Update(ref k, ref old, ref new) => MapDiff::Update(k.clone(), f(old), f(new))
+        }
+    }
+}
+
+impl<K,V> CellMap<K,V>
+    where K: Ord + Clone + Trace + Finalize + 'static,
+          V: Clone + Trace + Finalize + 'static
+{
+    pub fn hold<SC: IsStream<Vec<MapChange<K,V>>>>(initial: BTreeMap<K,V>, changes: SC) -> CellMap<K,V> {
+        let changes = changes.to_stream();
+        let snapshot = changes.accum(initial, |changes: &Vec<MapChange<K,V>>, map: &BTreeMap<K,V>| {
+            let mut map = map.clone();
+            for change in changes {
+                match *change {
+                    MapChange::Insert(ref k, ref v) => { map.insert(k.clone(), v.clone()); },
+                    MapChange::Remove(ref k) => { map.remove(k); }
+                }
+            }
+            map
+        });
+        // The snapshot is sampled before this transaction's changes apply.
+        let diffs = changes
+            .snapshot2(&snapshot, |changes: &Vec<MapChange<K,V>>, map: &BTreeMap<K,V>| changes_to_diffs(changes, map))
+            .filter(|diffs: &Vec<MapDiff<K,V>>| !diffs.is_empty());
+        CellMap {
+            diffs,
+            snapshot
+        }
+    }
+
+    /// Builds a `CellMap` that starts out as `initial` and then follows `diffs`.
+    pub fn from_diffs<SD: IsStream<Vec<MapDiff<K,V>>>>(initial: BTreeMap<K,V>, diffs: SD) -> CellMap<K,V> {
+        let diffs = diffs.to_stream();
+        let snapshot = diffs.accum(initial, |diffs: &Vec<MapDiff<K,V>>, map: &BTreeMap<K,V>| {
+            let mut map = map.clone();
+            for diff in diffs {
+                match *diff {
+                    MapDiff::Insert(ref k, ref v) => { map.insert(k.clone(), v.clone()); },
+                    MapDiff::Update(ref k, _, ref v) => { map.insert(k.clone(), v.clone()); },
+                    MapDiff::Remove(ref k, _) => { map.remove(k); }
+                }
+            }
+            map
+        });
+        CellMap {
+            diffs,
+            snapshot
+        }
+    }
+
+    pub fn diffs(&self) -> Stream<Vec<MapDiff<K,V>>> {
+        self.diffs.clone()
+    }
+
+    /// The full contents. The cell keeps the map of the previous transaction
+    /// until the new one commits, so every change copies the whole map and
+    /// costs O(n). Use `diffs` or the operators on `CellMap` where only the
+    /// changed entries matter.
+    pub fn snapshot(&self) -> Cell<BTreeMap<K,V>> {
+        self.snapshot.clone()
+    }
+
+    pub fn map<W: Clone + Trace + Finalize + 'static, F: Fn(&V)->W + 'static>(&self, f: F) -> CellMap<K,W> {
+        let f = Rc::new(f);
+        let initial = self.snapshot.sample().iter().map(|(k, v)| (k.clone(), f(v))).collect();
+        let diffs = self.diffs.map(move |diffs: &Vec<MapDiff<K,V>>| {
+            diffs.iter().map(|diff| diff.map_value(|v| f(v))).collect::<Vec<_>>()
+        });
+        CellMap::from_diffs(initial, diffs)
+    }
+
+    pub fn filter<PRED: Fn(&K,&V)->bool + 'static>(&self, pred: PRED) -> CellMap<K,V> {
+        let initial = self.snapshot.sample().into_iter().filter(|(k, v)| pred(k, v)).collect();
+        let diffs = self.diffs
+            .map(move |diffs: &Vec<MapDiff<K,V>>| {
+                diffs.iter().filter_map(|diff| match *diff {
+                    MapDiff::Insert(ref k, ref v) =>
+                        if pred(k, v) { Some(diff.clone()) } else { None },
+                    MapDiff::Remove(ref k, ref v) =>
+                        if pred(k, v) { Some(diff.clone()) } else { None },
+                    MapDiff::Update(ref k, ref old, ref new) =>
+                        match (pred(k, old), pred(k, new)) {
+                            (true, true) => Some(diff.clone()),
+                            (true, false) => Some(MapDiff::Remove(k.clone(), old.clone())),
+                            (false, true) => Some(MapDiff::Insert(k.clone(), new.clone())),
+                            (false, false) => None
+                        }
+                }).collect::<Vec<_>>()
+            })
+            .filter(|diffs: &Vec<MapDiff<K,V>>| !diffs.is_empty());
+        CellMap::from_diffs(initial, diffs)
+    }
+
+    /// Folds the entries into a single value, applying `add` for each
+    /// inserted entry and `remove` for each removed one. An update is a
+    /// `remove` of the old value followed by an `add` of the new one, so
+    /// `remove` must undo `add`.
+    pub fn fold<S,ADD,REMOVE>(&self, init: S, add: ADD, remove: REMOVE) -> Cell<S>
+        where S: Clone + Trace + Finalize + 'static,
+              ADD: Fn(&S,&K,&V)->S + 'static,
+              REMOVE: Fn(&S,&K,&V)->S + 'static
+    {
+        let initial = self.snapshot.sample().iter().fold(init, |s, (k, v)| add(&s, k, v));
+        self.diffs.accum(initial, move |diffs: &Vec<MapDiff<K,V>>, s: &S| {
+            let mut s = s.clone();
+            for diff in diffs {
+                s = match *diff {
+                    MapDiff::Insert(ref k, ref v) => add(&s, k, v),
+                    MapDiff::Remove(ref k, ref v) => remove(&s, k, v),
+                    MapDiff::Update(ref k, ref old, ref new) => add(&remove(&s, k, old), k, new)
+                };
+            }
+            s
+        })
+    }
+}
+
+impl<K,V> CellMap<K,Cell<V>>
+    where K: Ord + Clone + Trace + Finalize + 'static,
+          V: Clone + Trace + Finalize + 'static
+{
+    /// Flattens a map of cells into a map of their values. An update of an
+    /// inner cell becomes an `Update` diff for its key. Each inner cell is
+    /// subscribed on its own, so a structural change only touches the keys
+    /// it names.
+    pub fn flatten(&self) -> CellMap<K,V> {
+        let map = self.snapshot.sample();
+        let initial = map.iter().map(|(k, c)| (k.clone(), c.sample())).collect();
+        let structural = self.diffs.map(|diffs: &Vec<MapDiff<K,Cell<V>>>| {
+            diffs.iter().map(|diff| diff.map_value(|c| c.sample())).collect::<Vec<_>>()
+        });
+        let sodium_ctx = self.snapshot.impl_._node().sodium_ctx();
+        let subscriptions = self.diffs.map(|diffs: &Vec<MapDiff<K,Cell<V>>>| {
+            diffs.iter().map(|diff| match *diff {
+                MapDiff::Insert(ref k, ref c) => (k.clone(), Some(inner_updates(c))),
+                MapDiff::Update(ref k, _, ref c) => (k.clone(), Some(inner_updates(c))),
+                MapDiff::Remove(ref k, _) => (k.clone(), None)
+            }).collect::<Vec<_>>()
+        });
+        let inner = Stream {
+            impl_: impl_::Stream::merge_keyed(
+                &sodium_ctx,
+                map.iter().map(|(k, c)| (k.clone(), inner_updates(c))).collect(),
+                subscriptions.impl_
+            )
+        }.map(|updates: &Vec<(K,(V,V))>| {
+            updates.iter().map(|(k, (old, new))| MapDiff::Update(k.clone(), old.clone(), new.clone())).collect::<Vec<_>>()
+        });
+        // A structural diff already carries the value for its key, so an
+        // update of the replaced or removed cell in the same transaction is
+        // dropped.
+        let diffs = structural.merge(inner, |a: &Vec<MapDiff<K,V>>, b: &Vec<MapDiff<K,V>>| {
+            let mut result = a.clone();
+            result.extend(b.iter().filter(|diff| a.iter().all(|diff2| diff2.key() != diff.key())).cloned());
+            result
+        });
+        CellMap::from_diffs(initial, diffs)
+    }
+}
+
+fn inner_updates<V: Clone + Trace + Finalize + 'static>(c: &Cell<V>) -> impl_::Stream<(V,V)> {
+    let c2 = c.clone();
+    Operational::updates(c).map(move |v: &V| (c2.sample(), v.clone())).impl_
+}
+
+impl<K,V> Clone for CellMap<K,V>
+    where K: Ord + Clone + Trace + Finalize + 'static,
+          V: Clone + Trace + Finalize + 'static
+{
+    fn clone(&self) -> Self {
+        CellMap {
+            diffs: self.diffs.clone(),
+            snapshot: self.snapshot.clone()
+        }
+    }
+}
+
+fn changes_to_diffs<K: Ord + Clone, V: Clone>(changes: &Vec<MapChange<K,V>>, map: &BTreeMap<K,V>) -> Vec<MapDiff<K,V>> {
+    let mut overlay: BTreeMap<K,Option<V>> = BTreeMap::new();
+    let mut diffs = Vec::new();
+    for change in changes {
+        let current = match overlay.get(change_key(change)) {
+            Some(v_op) => v_op.clone(),
+            None => map.get(change_key(change)).cloned()
+        };
+        match *change {
+            MapChange::Insert(ref k, ref v) => {
+                diffs.push(match current {
+                    Some(old) => MapDiff::Update(k.clone(), old, v.clone()),
+                    None => MapDiff::Insert(k.clone(), v.clone())
+                });
+                overlay.insert(k.clone(), Some(v.clone()));
+            },
+            MapChange::Remove(ref k) => {
+                if let Some(old) = current {
+                    diffs.push(MapDiff::Remove(k.clone(), old));
+                }
+                overlay.insert(k.clone(), None);
+            }
+        }
+    }
+    diffs
+}
+
+fn change_key<K,V>(change: &MapChange<K,V>) -> &K {
+    match *change {
+        MapChange::Insert(ref k, _) => k,
+        MapChange::Remove(ref k) => k
+    }
+}
+
+impl<K: Trace, V: Trace> Trace for MapChange<K,V> {
+    fn trace(&self, f: &mut dyn FnMut(&GcDep)) {
+        match *self {
+            MapChange::Insert(ref k, ref v) => { k.trace(f); v.trace(f); },
+            MapChange::Remove(ref k) => k.trace(f)
+        }
+    }
+}
+
+impl<K: Finalize, V: Finalize> Finalize for MapChange<K,V> {
+    fn finalize(&mut self) {
+        match *self {
+            MapChange::Insert(ref mut k, ref mut v) => { k.finalize(); v.finalize(); },
+            MapChange::Remove(ref mut k) => k.finalize()
+        }
+    }
+}
+
+impl<K: Trace, V: Trace> Trace for MapDiff<K,V> {
+    fn trace(&self, f: &mut dyn FnMut(&GcDep)) {
+        match *self {
+            MapDiff::Insert(ref k, ref v) => { k.trace(f); v.trace(f); },
+            MapDiff::Remove(ref k, ref v) => { k.trace(f); v.trace(f); },
+            MapDiff::Update(ref k, ref old, ref new) => { k.trace(f); old.trace(f); new.trace(f); }
+        }
+    }
+}
+
+impl<K: Finalize, V: Finalize> Finalize for MapDiff<K,V> {
+    fn finalize(&mut self) {
+        match *self {
+            MapDiff::Insert(ref mut k, ref mut v) => { k.finalize(); v.finalize(); },
+            MapDiff::Remove(ref mut k, ref mut v) => { k.finalize(); v.finalize(); },
+            MapDiff::Update(ref mut k, ref mut old, ref mut new) => { k.finalize(); old.finalize(); new.finalize(); }
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use sodium::CallbackSendMode;
 use sodium::Cell;
+use sodium::SinkSender;
 use sodium::gc::Finalize;
 use sodium::gc::Trace;
 use sodium::impl_;
@@ -12,6 +14,18 @@ impl<A: Clone + Trace + Finalize + 'static> CellSink<A> {
         self.impl_.send(a.clone());
     }
 
+    pub fn callback_send_mode(&self) -> CallbackSendMode {
+        self.impl_.callback_send_mode()
+    }
+
+    pub fn set_callback_send_mode(&self, mode: CallbackSendMode) {
+        self.impl_.set_callback_send_mode(mode);
+    }
+
+    pub fn sender(&self) -> SinkSender<A> where A: Send {
+        self.impl_.sender()
+    }
+
     pub fn to_cell(&self) -> Cell<A> {
         Cell {
             impl_: self.impl_.to_cell()
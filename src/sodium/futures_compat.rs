@@ -0,0 +1,172 @@
+use futures;
+use futures::task::Context;
+use futures::task::Poll;
+use futures::task::Waker;
+use sodium::CellSink;
+use sodium::IsCell;
+use sodium::IsStream;
+use sodium::Listener;
+use sodium::StreamSink;
+use sodium::gc::Finalize;
+use sodium::gc::Trace;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// What a `ListenerStream` does with values that arrive faster than they
+/// are polled.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum BufferPolicy {
+    Unbounded,
+    /// Keep at most n values, discarding the oldest buffered value to make room.
+    /// A limit of 0 is taken as 1.
+    DropOldest(usize),
+    /// Keep at most n values, discarding new values while the buffer is full.
+    /// A limit of 0 is taken as 1.
+    DropNewest(usize),
+    /// Only keep the most recent value.
+    KeepLatest
+}
+
+/// A `futures::Stream` fed by a listener on a sodium `Stream` or `Cell`.
+/// The listener is removed when this is dropped. It never terminates on its
+/// own, as sodium streams have no end.
+pub struct ListenerStream<A> {
+    buffer: Rc<RefCell<Buffer<A>>>,
+    listener: Listener
+}
+
+struct Buffer<A> {
+    items: VecDeque<A>,
+    policy: BufferPolicy,
+    waker_op: Option<Waker>
+}
+
+impl<A> Buffer<A> {
+    fn push(&mut self, a: A) {
+        match self.policy {
+            BufferPolicy::Unbounded => {},
+            BufferPolicy::DropOldest(n) => {
+                while !self.items.is_empty() && self.items.len() >= n.max(1) {
+                    self.items.pop_front();
+                }
+            },
+            BufferPolicy::DropNewest(n) => {
+                if self.items.len() >= n.max(1) {
+                    return;
+                }
+            },
+            BufferPolicy::KeepLatest => {
+                self.items.clear();
+            }
+        }
+        self.items.push_back(a);
+        if let Some(waker) = self.waker_op.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<A: Clone + Trace + Finalize + 'static> ListenerStream<A> {
+    pub fn from_stream<SA: IsStream<A>>(sa: &SA, policy: BufferPolicy) -> ListenerStream<A> {
+        let buffer = ListenerStream::new_buffer(policy);
+        let listener;
+        {
+            let buffer = buffer.clone();
+            listener = sa.listen(move |a: &A| buffer.borrow_mut().push(a.clone()));
+        }
+        ListenerStream {
+            buffer,
+            listener
+        }
+    }
+
+    /// Yields the current value of the cell followed by each of its updates.
+    pub fn from_cell<CA: IsCell<A>>(ca: &CA, policy: BufferPolicy) -> ListenerStream<A> {
+        let buffer = ListenerStream::new_buffer(policy);
+        let listener;
+        {
+            let buffer = buffer.clone();
+            listener = ca.listen(move |a: &A| buffer.borrow_mut().push(a.clone()));
+        }
+        ListenerStream {
+            buffer,
+            listener
+        }
+    }
+
+    fn new_buffer(policy: BufferPolicy) -> Rc<RefCell<Buffer<A>>> {
+        Rc::new(RefCell::new(Buffer {
+            items: VecDeque::new(),
+            policy,
+            waker_op: None
+        }))
+    }
+}
+
+impl<A> futures::Stream for ListenerStream<A> {
+    type Item = A;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<A>> {
+        let mut buffer = self.buffer.borrow_mut();
+        match buffer.items.pop_front() {
+            Some(a) => Poll::Ready(Some(a)),
+            None => {
+                buffer.waker_op = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<A> Drop for ListenerStream<A> {
+    fn drop(&mut self) {
+        self.listener.unlisten();
+    }
+}
+
+// Sends are processed synchronously, so the sinks below are always ready.
+
+impl<A: Clone + Trace + Finalize + 'static> futures::Sink<A> for StreamSink<A> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, a: A) -> Result<(), Infallible> {
+        self.impl_.send(a);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<A: Clone + Trace + Finalize + 'static> futures::Sink<A> for CellSink<A> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, a: A) -> Result<(), Infallible> {
+        self.impl_.send(a);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+}
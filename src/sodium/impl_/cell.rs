@@ -13,6 +13,7 @@ use sodium::impl_::MemoLazy;
 use sodium::impl_::Node;
 use sodium::impl_::Operational;
 use sodium::impl_::SodiumCtx;
+use sodium::impl_::SodiumError;
 use sodium::impl_::Stream;
 use sodium::impl_::StreamData;
 use sodium::gc::Finalize;
@@ -141,6 +142,11 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
         thunk.get().clone()
     }
 
+    pub fn _value_thunk(&self) -> MemoLazy<A> {
+        let thunk = unsafe { &*(*self._value()).get() };
+        thunk.clone()
+    }
+
     pub fn _next_value_thunk(&self) -> MemoLazy<A> {
         let thunk_op = unsafe { &*(self._next_value()).get() };
         thunk_op.clone()
@@ -152,6 +158,14 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
         sodium_ctx.transaction(|| self.sample_no_trans())
     }
 
+    pub fn try_sample(&self) -> Result<A,SodiumError> {
+        let sodium_ctx = self._node().sodium_ctx();
+        if sodium_ctx.depends_on_unlooped_cell_loop(self._node()) {
+            return Err(SodiumError::SampledBeforeLooped);
+        }
+        Ok(sodium_ctx.transaction(|| self.sample_no_trans()))
+    }
+
     pub fn map<B: Clone + Trace + Finalize + 'static,F:IsLambda1<A,B> + 'static>(
         &self,
         f: F
@@ -312,6 +326,57 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
             )
     }
 
+    pub fn lift_all(sodium_ctx: &SodiumCtx, cells: Vec<Cell<A>>) -> Cell<Vec<A>> {
+        let node_deps = cells.iter().map(|c| c._node().clone()).collect();
+        let update_deps = cells.iter().map(|c| c.to_dep()).collect();
+        let cells2 = cells.clone();
+        Cell::lift_n(
+            sodium_ctx,
+            node_deps,
+            update_deps,
+            move || cells.iter().map(|c| c.sample_no_trans()).collect(),
+            move || {
+                let thunks: Vec<MemoLazy<A>> = cells2.iter().map(|c| c._next_value_thunk()).collect();
+                move || thunks.iter().map(|thunk| thunk.get().clone()).collect()
+            },
+            "Cell::lift_all"
+        )
+    }
+
+    // Builds a cell from any number of dependencies in a single node. next is
+    // called when a dependency updates, and should take its dependencies' next
+    // value thunks, returning a function that computes the new value from them.
+    pub fn lift_n<INIT,NEXT,THUNK>(
+        sodium_ctx: &SodiumCtx,
+        node_deps: Vec<Node>,
+        update_deps: Vec<Dep>,
+        init: INIT,
+        next: NEXT,
+        desc: &'static str
+    ) -> Cell<A>
+        where INIT: Fn()->A + 'static,
+              NEXT: Fn()->THUNK + 'static,
+              THUNK: Fn()->A + 'static
+    {
+        let init_value = sodium_ctx.new_lazy(init);
+        let sodium_ctx2 = sodium_ctx.clone();
+        let update = Lambda::new(
+            move || {
+                let sodium_ctx = &sodium_ctx2;
+                Some(sodium_ctx.new_lazy(next()))
+            },
+            update_deps
+        );
+        Cell::_new(
+            sodium_ctx,
+            init_value,
+            update,
+            node_deps,
+            || {},
+            desc
+        )
+    }
+
     pub fn switch_s(csa: Cell<Stream<A>>) -> Stream<A> {
         let sodium_ctx = csa._node().sodium_ctx();
         let sodium_ctx = &sodium_ctx;
@@ -329,8 +394,8 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
                 sodium_ctx,
                 move || {
                     let sodium_ctx = &sodium_ctx2;
-                    let csa_next_value = csa._next_value_thunk();
-                    let sa = csa_next_value.get().clone();
+                    // The inner stream only changes at the end of the transaction.
+                    let sa = csa.sample_no_trans();
                     if let Some(sa_value) = sa.peek_value() {
                         {
                             let value = unsafe { &mut *(*value).get() };
@@ -459,6 +524,29 @@ impl<A: Clone + Trace + Finalize + 'static> Cell<A> {
     }
 }
 
+impl<A: Clone + Trace + Finalize + PartialEq + 'static> Cell<A> {
+    /// A cell that only updates when its value changes.
+    pub fn calm(&self) -> Cell<A> {
+        let sodium_ctx = self._node().sodium_ctx();
+        let sodium_ctx = &sodium_ctx;
+        sodium_ctx.transaction(|| {
+            let init;
+            let init_state;
+            {
+                let self_ = self.clone();
+                init = sodium_ctx.new_lazy(Lambda::new(move || self_.sample_no_trans(), vec![self.to_dep()]));
+            }
+            {
+                let self_ = self.clone();
+                init_state = sodium_ctx.new_lazy(Lambda::new(move || Some(self_.sample_no_trans()), vec![self.to_dep()]));
+            }
+            Operational::updates(self.clone())
+                .calm_lazy(init_state)
+                .hold_lazy(init)
+        })
+    }
+}
+
 impl<A: Clone + 'static> Clone for Cell<A> {
     fn clone(&self) -> Self {
         Cell {
@@ -2,7 +2,9 @@ use sodium::impl_::Dep;
 use sodium::impl_::MemoLazy;
 use sodium::impl_::Node;
 use sodium::impl_::SodiumCtx;
+use sodium::impl_::SodiumError;
 use sodium::impl_::Cell;
+use sodium::impl_::SAMPLED_BEFORE_LOOPED;
 use sodium::impl_::gc::Finalize;
 use sodium::impl_::gc::Gc;
 use sodium::impl_::gc::Trace;
@@ -27,11 +29,23 @@ impl<A: Trace + Finalize + Clone + 'static> CellLoop<A> {
                     if let &Some(ref val) = init_value {
                         val.clone()
                     } else {
-                        panic!("CellLoop sampled before looped.")
+                        panic!("{}", SAMPLED_BEFORE_LOOPED)
                     }
                 })
             );
         }
+        {
+            let id = cell._node().id();
+            let sodium_ctx2 = sodium_ctx.clone();
+            sodium_ctx.add_unlooped_cell_loop(id);
+            cell._node().add_cleanup(move || sodium_ctx2.remove_unlooped_cell_loop(id));
+        }
+        {
+            let init_value = Rc::downgrade(&init_value);
+            sodium_ctx.register_loop(cell._node().id(), move || {
+                init_value.upgrade().map(|init_value| unsafe { &*(*init_value).get() }.is_some()).unwrap_or(true)
+            });
+        }
         CellLoop {
             cell,
             init_value
@@ -39,11 +53,18 @@ impl<A: Trace + Finalize + Clone + 'static> CellLoop<A> {
     }
 
     pub fn loop_(&self, ca: Cell<A>) {
+        if self.try_loop(ca).is_err() {
+            panic!("CellLoop looped more than once.");
+        }
+    }
+
+    pub fn try_loop(&self, ca: Cell<A>) -> Result<(),SodiumError> {
         let init_value = unsafe { &mut *(*self.init_value).get() };
         if init_value.is_some() {
-            panic!("CellLoop looped more than once.");
+            return Err(SodiumError::AlreadyLooped);
         }
         *init_value = Some(ca.sample_no_trans());
+        self.cell._node().sodium_ctx().remove_unlooped_cell_loop(self.cell._node().id());
         let value = self.cell._value().clone();
         let next_value = self.cell._next_value().clone();
         let update_deps = vec![ca.to_dep(), Dep { gc_dep: next_value.to_dep() }];
@@ -75,6 +96,12 @@ impl<A: Trace + Finalize + Clone + 'static> CellLoop<A> {
         );
         node.ensure_bigger_than(ca_node.rank());
         node.add_dependencies(vec![ca_node]);
+        Ok(())
+    }
+
+    pub fn is_looped(&self) -> bool {
+        let init_value = unsafe { &*(*self.init_value).get() };
+        init_value.is_some()
     }
 
     pub fn to_cell(&self) -> Cell<A> {
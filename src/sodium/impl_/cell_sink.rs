@@ -1,20 +1,30 @@
+use sodium::impl_::CallbackSendMode;
 use sodium::impl_::Cell;
 use sodium::impl_::Dep;
 use sodium::impl_::Lambda;
 use sodium::impl_::MemoLazy;
+use sodium::impl_::SendHook;
+use sodium::impl_::SinkSender;
 use sodium::impl_::SodiumCtx;
 use sodium::gc::Finalize;
 use sodium::gc::Gc;
 use sodium::gc::Trace;
 use std::cell::UnsafeCell;
+use std::rc::Rc;
 
 pub struct CellSink<A> {
     next_value_op: Gc<UnsafeCell<Option<MemoLazy<A>>>>,
-    cell: Cell<A>
+    cell: Cell<A>,
+    callback_send_mode_op: Rc<UnsafeCell<Option<CallbackSendMode>>>,
+    send_hooks: Rc<UnsafeCell<Vec<SendHook<A>>>>
 }
 
 impl<A: Trace + Finalize + Clone + 'static> CellSink<A> {
     pub fn new(sodium_ctx: &SodiumCtx, value: A) -> CellSink<A> {
+        CellSink::new_lazy(sodium_ctx, sodium_ctx.new_lazy(move || value.clone()))
+    }
+
+    pub fn new_lazy(sodium_ctx: &SodiumCtx, value: MemoLazy<A>) -> CellSink<A> {
         let mut gc_ctx = sodium_ctx.gc_ctx();
         let next_value_op = gc_ctx.new_gc_with_desc(UnsafeCell::new(None), String::from("CellSink::new_next_value"));
         let deps = vec![Dep { gc_dep: next_value_op.to_dep() }];
@@ -22,7 +32,7 @@ impl<A: Trace + Finalize + Clone + 'static> CellSink<A> {
             next_value_op: next_value_op.clone(),
             cell: Cell::_new(
                 sodium_ctx,
-                sodium_ctx.new_lazy(move || value.clone()),
+                value,
                 Lambda::new(
                     move || {
                         let next_value_op = unsafe { &*(*next_value_op).get() };
@@ -33,22 +43,59 @@ impl<A: Trace + Finalize + Clone + 'static> CellSink<A> {
                 Vec::new(),
                 || {},
                 "CellSink::new"
-            )
+            ),
+            callback_send_mode_op: Rc::new(UnsafeCell::new(None)),
+            send_hooks: Rc::new(UnsafeCell::new(Vec::new()))
         }
     }
 
     pub fn send(&self, value: A) {
         let sodium_ctx = self.cell._node().sodium_ctx();
         if sodium_ctx.callback_depth() > 0 {
-            panic!("StreamSink::send / CellSink::send can not be called from a sodium callback, consider using SodiumCtx::post to send after the end of transaction.")
+            match self.callback_send_mode() {
+                CallbackSendMode::Panic =>
+                    panic!("StreamSink::send / CellSink::send can not be called from a sodium callback, consider using SodiumCtx::post to send after the end of transaction, or CallbackSendMode::Defer."),
+                CallbackSendMode::Defer => {
+                    let self_ = self.clone();
+                    sodium_ctx.defer_send(move || self_.send(value));
+                    return;
+                }
+            }
         }
         sodium_ctx.transaction(|| {
+            if !sodium_ctx.is_propergating() {
+                let send_hooks = unsafe { &*(*self.send_hooks).get() };
+                for send_hook in send_hooks {
+                    send_hook(&value, sodium_ctx.transaction_seq());
+                }
+            }
             let next_value_op = unsafe { &mut *(*self.next_value_op).get() };
             *next_value_op = Some(sodium_ctx.new_lazy(move || value.clone()));
             self.cell._node().mark_dirty();
         });
     }
 
+    /// Calls hook with each value sent from outside of a transaction's
+    /// propagation, along with the transaction's `transaction_seq`.
+    pub fn add_send_hook<HOOK: Fn(&A,u64) + 'static>(&self, hook: HOOK) {
+        let send_hooks = unsafe { &mut *(*self.send_hooks).get() };
+        send_hooks.push(Box::new(hook));
+    }
+
+    pub fn callback_send_mode(&self) -> CallbackSendMode {
+        let callback_send_mode_op = unsafe { &*(*self.callback_send_mode_op).get() };
+        callback_send_mode_op.unwrap_or_else(|| self.cell._node().sodium_ctx().callback_send_mode())
+    }
+
+    pub fn set_callback_send_mode(&self, mode: CallbackSendMode) {
+        unsafe { *(*self.callback_send_mode_op).get() = Some(mode); }
+    }
+
+    pub fn sender(&self) -> SinkSender<A> where A: Send {
+        let self_ = self.clone();
+        self.cell._node().sodium_ctx().new_sink_sender(move |a: A| self_.send(a))
+    }
+
     pub fn to_cell(&self) -> Cell<A> {
         self.cell.clone()
     }
@@ -58,7 +105,9 @@ impl<A: Trace + Finalize + Clone + 'static> Clone for CellSink<A> {
     fn clone(&self) -> Self {
         CellSink {
             next_value_op: self.next_value_op.clone(),
-            cell: self.cell.clone()
+            cell: self.cell.clone(),
+            callback_send_mode_op: self.callback_send_mode_op.clone(),
+            send_hooks: self.send_hooks.clone()
         }
     }
 }
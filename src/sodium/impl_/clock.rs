@@ -0,0 +1,72 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now()
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+pub struct VirtualClock {
+    now: Rc<Cell<Duration>>
+}
+
+impl VirtualClock {
+    pub fn new() -> VirtualClock {
+        VirtualClock {
+            now: Rc::new(Cell::new(Duration::from_millis(0)))
+        }
+    }
+
+    pub fn set_now(&self, now: Duration) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+impl Clone for VirtualClock {
+    fn clone(&self) -> Self {
+        VirtualClock {
+            now: self.now.clone()
+        }
+    }
+}
@@ -12,8 +12,13 @@ use std::cell::Cell;
 use std::cell::RefCell;
 use std::cell::UnsafeCell;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::hash::Hash;
+use std::marker::PhantomData;
 use std::collections::{BinaryHeap, BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDeque};
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 
 pub struct GcCtx {
     data: Rc<RefCell<GcCtxData>>
@@ -30,7 +35,44 @@ impl Clone for GcCtx {
 struct GcCtxData {
     roots: Vec<*mut Node>,
     collecting_cycles: bool,
-    to_be_freed: Vec<*mut Node>
+    to_be_freed: Vec<*mut Node>,
+    in_transaction: bool,
+    policy: CollectionPolicy,
+    stats: GcStats
+}
+
+/// When a `GcCtx` looks for garbage cycles. Values that are not part of a
+/// cycle are freed as soon as they are dropped, whatever the policy. While a
+/// `SodiumCtx` transaction is open no collection is run on drop, the
+/// candidates are instead looked at once after the transaction's post phase.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum CollectionPolicy {
+    /// On every drop of a `Gc`, and at the end of each transaction.
+    EveryDrop,
+    /// Once this many candidate roots have been buffered, checked on drop and
+    /// at the end of each transaction.
+    EveryNRoots(usize),
+    /// Only when `collect_cycles` is called.
+    Manual,
+    /// When the outermost `SodiumCtx` transaction ends.
+    EndOfTransaction
+}
+
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct GcStats {
+    /// `Gc`s allocated so far.
+    pub allocated: u64,
+    /// `Gc`s allocated and not yet freed.
+    pub live: u64,
+    /// `Gc`s freed so far.
+    pub freed: u64,
+    /// Values buffered as possible roots of a garbage cycle, waiting for the
+    /// next collection.
+    pub candidate_roots: usize,
+    /// Cycle collections that had candidate roots to look at.
+    pub collections: u64,
+    /// Time spent in those collections.
+    pub collection_time: Duration
 }
 
 pub struct GcDep {
@@ -38,6 +80,16 @@ pub struct GcDep {
     node: *mut Node
 }
 
+/// One value of a `GcCtx::trace_graph` snapshot.
+#[derive(Clone,Debug,PartialEq)]
+pub struct TracedGc {
+    pub id: usize,
+    pub desc: Option<String>,
+    pub strong: i32,
+    /// Ids of the values this one traces, one entry per reference.
+    pub edges: Vec<usize>
+}
+
 impl Clone for GcDep {
     fn clone(&self) -> Self {
         let n: &mut Node = unsafe { &mut *self.node };
@@ -49,6 +101,13 @@ impl Clone for GcDep {
     }
 }
 
+impl GcDep {
+    /// Identifies the value within a `GcCtx::trace_graph` snapshot.
+    pub fn id(&self) -> usize {
+        self.node as usize
+    }
+}
+
 impl Drop for GcDep {
     fn drop(&mut self) {
         let n: &mut Node = unsafe { &mut *self.node };
@@ -76,7 +135,7 @@ impl<A: ?Sized> Clone for Gc<A> {
 impl<A: ?Sized> Drop for Gc<A> {
     fn drop(&mut self) {
         self.ctx.decrement(self.node);
-        self.ctx.collect_cycles();
+        self.ctx.dropped();
     }
 }
 
@@ -108,6 +167,11 @@ impl<A: ?Sized> Gc<A> {
         node.strong
     }
 
+    pub fn desc(&self) -> Option<&str> {
+        let node = unsafe { &*self.node };
+        node.desc_op.as_deref()
+    }
+
     pub fn weak_count(&self) -> i32 {
         let node = unsafe { &*self.node };
         node.weak
@@ -192,6 +256,11 @@ pub trait Finalize {
     }
 }
 
+#[cfg(feature = "derive")]
+pub use sodium_derive::Finalize;
+#[cfg(feature = "derive")]
+pub use sodium_derive::Trace;
+
 pub struct NoGc<A:?Sized>(A);
 
 impl<A> NoGc<A> {
@@ -215,22 +284,6 @@ impl<A:?Sized> DerefMut for NoGc<A> {
     }
 }
 
-#[cfg(feature = "nightly")]
-default impl<T> Trace for T {
-    #[inline]
-    default fn trace(&self, _tracer: &mut FnMut(&GcDep)) {
-        // default impl: Do nothing.
-    }
-}
-
-#[cfg(feature = "nightly")]
-default impl<T> Finalize for T {
-    #[inline]
-    default fn finalize(&mut self) {
-        // default impl: Do nothing.
-    }
-}
-
 impl<A> Trace for NoGc<A> {
     fn trace(&self, _tracer: &mut FnMut(&GcDep)) {}
 }
@@ -243,23 +296,37 @@ impl<A:Clone> Clone for NoGc<A> {
     }
 }
 
-macro_rules! mk_empty_finalize_trace {
-    ($($T:ty),*) => {
+/// Implements empty `Trace` and `Finalize` for types that can never hold a
+/// sodium object, e.g. `impl_no_gc!(MyStruct, MyEnum);`.
+#[macro_export]
+macro_rules! impl_no_gc {
+    ($($T:ty),* $(,)*) => {
         $(
-            impl Finalize for $T {}
-            impl Trace for $T {
-                fn trace(&self, _tracer: &mut FnMut(&GcDep)) {
+            impl $crate::sodium::gc::Finalize for $T {}
+            impl $crate::sodium::gc::Trace for $T {
+                fn trace(&self, _tracer: &mut dyn FnMut(&$crate::sodium::gc::GcDep)) {
                 }
             }
         )*
     }
 }
 
-mk_empty_finalize_trace![(), isize, usize, bool, i8, u8, i16, u16, i32,
-    u32, i64, u64, f32, f64, char, String];
+impl_no_gc![(), isize, usize, bool, i8, u8, i16, u16, i32, u32, i64, u64,
+    i128, u128, f32, f64, char, String, Box<str>, Rc<str>, Arc<str>,
+    Duration, Instant, SystemTime];
+
+impl<A> Trace for PhantomData<A> {
+    fn trace(&self, _tracer: &mut dyn FnMut(&GcDep)) {}
+}
+
+impl<A> Finalize for PhantomData<A> {}
+
+// A Copy type can not own a Gc, so there is nothing to visit.
+impl<A: Copy> Trace for Cell<A> {
+    fn trace(&self, _tracer: &mut dyn FnMut(&GcDep)) {}
+}
 
-#[cfg(feature = "nightly")]
-mk_empty_finalize_trace![i128, u128];
+impl<A: Copy> Finalize for Cell<A> {}
 
 impl<A: Trace> Trace for Gc<A> {
     fn trace(&self, f: &mut FnMut(&GcDep)) {
@@ -292,12 +359,25 @@ impl<A: Trace, E: Trace> Trace for Result<A,E> {
     }
 }
 
+// There are deliberately no impls for Rc, Arc or RefCell. Contents shared
+// between handles would be visited once per handle, letting trial deletion
+// take a live value's count to zero, and a RefCell can not be read while it
+// is mutably borrowed. Hold such state in a Gc / GcCell, or in a NoGc when it
+// never holds sodium objects.
 impl<A: Trace> Trace for Box<A> {
     fn trace(&self, tracer: &mut FnMut(&GcDep)) {
         (**self).trace(tracer);
     }
 }
 
+impl<A: Trace, const N: usize> Trace for [A; N] {
+    fn trace(&self, tracer: &mut dyn FnMut(&GcDep)) {
+        for a in self {
+            a.trace(tracer);
+        }
+    }
+}
+
 impl<A: Trace> Trace for Vec<A> {
     fn trace(&self, f: &mut FnMut(&GcDep)) {
         for a in self {
@@ -463,6 +543,14 @@ impl<A: Finalize> Finalize for Box<A> {
     }
 }
 
+impl<A: Finalize, const N: usize> Finalize for [A; N] {
+    fn finalize(&mut self) {
+        for a in self {
+            a.finalize();
+        }
+    }
+}
+
 impl<A: Finalize> Finalize for Vec<A> {
     fn finalize(&mut self) {
         for a in self {
@@ -816,7 +904,10 @@ impl GcCtx {
                 GcCtxData {
                     roots: Vec::new(),
                     collecting_cycles: false,
-                    to_be_freed: Vec::new()
+                    to_be_freed: Vec::new(),
+                    in_transaction: false,
+                    policy: CollectionPolicy::EveryDrop,
+                    stats: GcStats::default()
                 }
             ))
         }
@@ -830,7 +921,103 @@ impl GcCtx {
         self._new_gc(value, Some(desc))
     }
 
+    pub fn collection_policy(&self) -> CollectionPolicy {
+        self.with_data(|data| data.policy)
+    }
+
+    pub fn set_collection_policy(&self, policy: CollectionPolicy) {
+        self.with_data(|data| data.policy = policy);
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.with_data(|data| {
+            let mut stats = data.stats;
+            stats.live = stats.allocated - stats.freed;
+            stats.candidate_roots = data.roots.len();
+            stats
+        })
+    }
+
+    /// Every value reachable from `from` through `Trace`, along with its
+    /// strong count. References held from outside of these values show up as
+    /// strong counts higher than the number of edges pointing in.
+    pub fn trace_graph(&self, from: &[GcDep]) -> Vec<TracedGc> {
+        let mut result = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<*mut Node> = from.iter().map(|dep| dep.node).collect();
+        while let Some(s) = stack.pop() {
+            if !visited.insert(s) {
+                continue;
+            }
+            let node = unsafe { &*s };
+            let mut edges = Vec::new();
+            node.trace(&mut |t| {
+                edges.push(t as usize);
+                stack.push(t);
+            });
+            result.push(TracedGc {
+                id: s as usize,
+                desc: node.desc_op.clone(),
+                strong: node.strong,
+                edges
+            });
+        }
+        result
+    }
+
+    /// Called by `SodiumCtx` when its outermost transaction starts. Until
+    /// `transaction_end`, drops only free values that are not part of a
+    /// cycle, so building a graph does not scan it again on every drop.
+    pub fn transaction_start(&self) {
+        self.with_data(|data| data.in_transaction = true);
+    }
+
+    /// Called by `SodiumCtx` once the post phase of its outermost transaction
+    /// has run.
+    pub fn transaction_end(&self) {
+        let collect = self.with_data(|data| {
+            data.in_transaction = false;
+            match data.policy {
+                CollectionPolicy::EveryDrop | CollectionPolicy::EndOfTransaction => true,
+                CollectionPolicy::EveryNRoots(n) => data.roots.len() >= n,
+                CollectionPolicy::Manual => false
+            }
+        });
+        if collect {
+            self.collect_cycles();
+        } else {
+            self.free_released();
+        }
+    }
+
+    fn dropped(&self) {
+        let collect = self.with_data(|data| !data.in_transaction && match data.policy {
+            CollectionPolicy::EveryDrop => true,
+            CollectionPolicy::EveryNRoots(n) => data.roots.len() >= n,
+            CollectionPolicy::Manual | CollectionPolicy::EndOfTransaction => false
+        });
+        if collect {
+            self.collect_cycles();
+        } else {
+            self.free_released();
+        }
+    }
+
+    // Frees the values whose counts dropped to zero, without looking for
+    // cycles.
+    fn free_released(&self) {
+        if self.with_data(|data| data.collecting_cycles) {
+            return;
+        }
+        self.with_data(|data| data.collecting_cycles = true);
+        while self.with_data(|data| !data.to_be_freed.is_empty()) {
+            self.free_to_be_freed();
+        }
+        self.with_data(|data| data.collecting_cycles = false);
+    }
+
     fn _new_gc<A: Trace + Finalize + 'static>(&mut self, value: A, desc_op: Option<String>) -> Gc<A> {
+        self.with_data(|data| data.stats.allocated += 1);
         let value = Box::into_raw(Box::new(value));
         let value2 = value.clone();
         let value3 = value.clone();
@@ -895,6 +1082,7 @@ impl GcCtx {
         debug_assert!(s.strong == 0);
         (s.cleanup)();
         s.freed = true;
+        self.with_data(|data| data.stats.freed += 1);
         if s.weak > 0 {
             s.weak = s.weak - 1;
             if s.weak == 0 {
@@ -925,20 +1113,32 @@ impl GcCtx {
             return;
         }
         self.with_data(|data| data.collecting_cycles = true);
+        let mut start_op = None;
+
+        loop {
+            if self.with_data(|data| !data.roots.is_empty()) {
+                start_op = start_op.or_else(|| Some(Instant::now()));
+                self.mark_roots();
+                self.scan_roots();
+                self.collect_roots();
+            }
 
-        self.mark_roots();
-        self.scan_roots();
-        self.collect_roots();
-
-        let again = self.with_data(|data| data.to_be_freed.len() != 0);
-
-        self.free_to_be_freed();
+            let again = self.with_data(|data| !data.to_be_freed.is_empty());
 
-        self.with_data(|data| data.collecting_cycles = false);
+            self.free_to_be_freed();
 
-        if again {
-            self.collect_cycles();
+            if !again {
+                break;
+            }
         }
+
+        self.with_data(|data| {
+            data.collecting_cycles = false;
+            if let Some(start) = start_op {
+                data.stats.collections += 1;
+                data.stats.collection_time += start.elapsed();
+            }
+        });
     }
 
     fn mark_roots(&self) {
@@ -976,6 +1176,10 @@ impl GcCtx {
             data.roots.clear();
             roots
         });
+        let mut visited = HashSet::new();
+        for s in &roots {
+            self.restore_external(*s, &mut visited);
+        }
         for s in roots {
             let s = unsafe { &mut *s };
             s.buffered = false;
@@ -983,6 +1187,24 @@ impl GcCtx {
         }
     }
 
+    // mark_gray took the white nodes' references off the counts of their
+    // live children, and freeing the white nodes drops those references
+    // again. Put the counts back so the children are only decremented once.
+    fn restore_external(&self, s: *mut Node, visited: &mut HashSet<*mut Node>) {
+        let s = unsafe { &mut *s };
+        if s.colour != Colour::White || !visited.insert(s) {
+            return;
+        }
+        s.trace(&mut |t| {
+            let t2 = unsafe { &mut *t };
+            if t2.colour == Colour::White {
+                self.restore_external(t, visited);
+            } else {
+                t2.strong += 1;
+            }
+        });
+    }
+
     fn mark_gray(&self, s: *mut Node) {
         let s = unsafe { &mut *s };
         if s.colour != Colour::Gray {
@@ -0,0 +1,50 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// The live nodes of a `SodiumCtx`, as returned by `SodiumCtx::dump_graph`.
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct GraphDump {
+    pub nodes: Vec<NodeDump>
+}
+
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct NodeDump {
+    pub id: u32,
+    pub desc: String,
+    pub rank: u32,
+    /// Ids of the nodes this node depends on.
+    pub dependencies: Vec<u32>,
+    /// Whether a listener is keeping this node alive.
+    pub kept_alive: bool
+}
+
+impl GraphDump {
+    pub fn node(&self, id: u32) -> Option<&NodeDump> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Renders the graph in Graphviz DOT format, with edges pointing from a
+    /// dependency to its dependent. Kept alive nodes are drawn bold.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph sodium {\n");
+        for node in &self.nodes {
+            dot.push_str(&format!(
+                "    n{} [label=\"{} {}\\nrank {}\"{}];\n",
+                node.id,
+                node.id,
+                node.desc.replace('\\', "\\\\").replace('"', "\\\""),
+                node.rank,
+                if node.kept_alive { ", style=bold" } else { "" }
+            ));
+        }
+        for node in &self.nodes {
+            for dependency in &node.dependencies {
+                dot.push_str(&format!("    n{} -> n{};\n", dependency, node.id));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+
+/// The nodes of a `SodiumCtx` that are still alive, as returned by
+/// `SodiumCtx::leak_report`, each with what keeps it alive.
+#[derive(Clone,Debug,PartialEq)]
+pub struct LeakReport {
+    pub nodes: Vec<LeakedNode>
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct LeakedNode {
+    pub id: u32,
+    pub desc: String,
+    pub retention: Retention
+}
+
+/// Why a node is alive. A path starts at the value holding on to the graph
+/// and ends at the node itself, naming each value on the way by its `desc`.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Retention {
+    /// A listener that was never unlistened, through the context's
+    /// `keep_alive` set.
+    Listener { path: Vec<String> },
+    /// A reference from outside the graph, such as a `Stream`, `Cell` or
+    /// `Gc` still held by user code.
+    Held { path: Vec<String> },
+    /// Nothing outside the graph refers to it, it is part of a garbage cycle
+    /// that has not been collected yet.
+    Cycle
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} nodes are remaining", self.nodes.len())?;
+        for node in &self.nodes {
+            write!(f, "\n  {} {}: ", node.id, node.desc)?;
+            match node.retention {
+                Retention::Listener { ref path } => write!(f, "kept alive by a listener: {}", path.join(" -> "))?,
+                Retention::Held { ref path } => write!(f, "held from outside the graph: {}", path.join(" -> "))?,
+                Retention::Cycle => write!(f, "uncollected garbage cycle")?
+            }
+        }
+        Ok(())
+    }
+}
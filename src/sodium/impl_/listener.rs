@@ -39,6 +39,89 @@ impl Listener {
         }
         *node_op = None;
     }
+
+    /// Unlistens when the returned guard is dropped.
+    pub fn guard(self) -> ListenerGuard {
+        ListenerGuard {
+            listener_op: Some(self)
+        }
+    }
+
+    /// Gives up the ability to unlisten, so the listener stays for as long
+    /// as the `SodiumCtx` does. Dropping a `Listener` does the same, this
+    /// makes it explicit.
+    pub fn detach(self) {
+    }
+}
+
+/// Owns a `Listener` and unlistens it on drop.
+pub struct ListenerGuard {
+    listener_op: Option<Listener>
+}
+
+impl ListenerGuard {
+    pub fn unlisten(mut self) {
+        if let Some(listener) = self.listener_op.take() {
+            listener.unlisten();
+        }
+    }
+
+    /// Takes the listener back out without unlistening it.
+    pub fn into_listener(mut self) -> Listener {
+        self.listener_op.take().unwrap()
+    }
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener_op.take() {
+            listener.unlisten();
+        }
+    }
+}
+
+/// Holds many listeners and unlistens them together, either through
+/// `unlisten_all` or when dropped.
+pub struct ListenerSet {
+    listeners: Vec<Listener>
+}
+
+impl ListenerSet {
+    pub fn new() -> ListenerSet {
+        ListenerSet {
+            listeners: Vec::new()
+        }
+    }
+
+    pub fn add(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+
+    pub fn len(&self) -> usize {
+        self.listeners.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    pub fn unlisten_all(&mut self) {
+        for listener in self.listeners.drain(..) {
+            listener.unlisten();
+        }
+    }
+}
+
+impl Default for ListenerSet {
+    fn default() -> Self {
+        ListenerSet::new()
+    }
+}
+
+impl Drop for ListenerSet {
+    fn drop(&mut self) {
+        self.unlisten_all();
+    }
 }
 
 impl Finalize for Listener {
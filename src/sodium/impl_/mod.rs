@@ -1,7 +1,12 @@
 pub use self::cell::Cell;
 pub use self::cell_loop::CellLoop;
 pub use self::cell_sink::CellSink;
+pub use self::clock::Clock;
+pub use self::clock::SystemClock;
+pub use self::clock::VirtualClock;
 pub use self::dep::Dep;
+pub use self::graph_dump::GraphDump;
+pub use self::graph_dump::NodeDump;
 pub use self::lambda::Lambda;
 pub use self::lambda::IsLambda0;
 pub use self::lambda::IsLambdaMut0;
@@ -12,33 +17,57 @@ pub use self::lambda::IsLambda4;
 pub use self::lambda::IsLambda5;
 pub use self::lambda::IsLambda6;
 pub use self::latch::Latch;
+pub use self::leak_report::LeakReport;
+pub use self::leak_report::LeakedNode;
+pub use self::leak_report::Retention;
 pub use self::listener::Listener;
+pub use self::listener::ListenerGuard;
+pub use self::listener::ListenerSet;
 pub use self::memo_lazy::MemoLazy;
 pub use self::node::Node;
+pub use self::node::WeakNode;
 pub use self::operational::Operational;
+pub use self::sink_sender::PendingItem;
+pub use self::sink_sender::PendingQueue;
+pub use self::sink_sender::PendingSend;
+pub use self::sink_sender::SinkSender;
+pub use self::sodium_ctx::CallbackSendMode;
 pub use self::sodium_ctx::SodiumCtx;
 pub use self::sodium_ctx::SodiumCtxData;
 pub use self::sodium_ctx::WeakSodiumCtx;
+pub use self::sodium_error::SodiumError;
+pub use self::sodium_error::SAMPLED_BEFORE_LOOPED;
 pub use self::stream::Stream;
 pub use self::stream::StreamData;
 pub use self::stream_loop::StreamLoop;
+pub use self::stream_sink::SendHook;
 pub use self::stream_sink::StreamSink;
+pub use self::timer_system::TimerSystem;
+pub use self::transaction_observer::HookKind;
+pub use self::transaction_observer::TransactionObserver;
 
 mod cell;
 mod cell_loop;
 mod cell_sink;
+mod clock;
 mod dep;
+mod graph_dump;
 pub mod gc;
 
 #[macro_use]
 mod lambda;
 
 mod latch;
+mod leak_report;
 mod listener;
 mod memo_lazy;
 mod node;
 mod operational;
+mod sink_sender;
 mod sodium_ctx;
+mod sodium_error;
 mod stream;
 mod stream_loop;
 mod stream_sink;
+mod timer_system;
+mod transaction_observer;
@@ -46,6 +46,9 @@ impl Node {
         mut cleanup: CLEANUP,
         desc: String
     ) -> Node {
+        for dependency in &dependencies {
+            sodium_ctx.assert_same_ctx(dependency, &desc);
+        }
         let id = sodium_ctx.new_id();
         sodium_ctx.inc_node_count();
         let mut rank = 0;
@@ -114,6 +117,7 @@ impl Node {
                 }
             ), desc)
         };
+        sodium_ctx.register_node(&node);
         unsafe {
             *(*self_).get() = Some(node.downgrade());
         };
@@ -163,10 +167,30 @@ impl Node {
         data.dependencies.clear();
     }
 
+    pub fn remove_dependency(&self, dependency: &Node) {
+        let data = unsafe { &mut *(*self.data).get() };
+        let self_id = data.id;
+        {
+            let dependency = unsafe { &mut *(*dependency.data).get() };
+            dependency.dependents.retain(|weak_node| {
+                match weak_node.upgrade() {
+                    Some(node2) => {
+                        let node2_data = unsafe { &*(*node2.data).get() };
+                        node2_data.id != self_id
+                    },
+                    None => false
+                }
+            });
+        }
+        let dependency_id = dependency.id();
+        data.dependencies.retain(|dependency2| dependency2.id() != dependency_id);
+    }
+
     pub fn add_dependencies(&self, dependencies: Vec<Node>) {
         let data = unsafe { &mut *(*self.data).get() };
         let weak_node = self.downgrade();
         for dependency in dependencies {
+            data.sodium_ctx.assert_same_ctx(&dependency, self.desc_str());
             {
                 let dependency = unsafe { &mut *(*dependency.data).get() };
                 dependency.dependents.push(weak_node.clone());
@@ -196,6 +220,9 @@ impl Node {
         if !sodium_ctx.to_be_updated_set.contains(self) {
             sodium_ctx.to_be_updated.push(self.clone());
             sodium_ctx.to_be_updated_set.insert(self.clone());
+            if self_.sodium_ctx.has_transaction_observers() {
+                self_.sodium_ctx.notify_observers(|observer| observer.node_marked_dirty(self_.id, self.desc_str(), self_.rank));
+            }
         }
     }
 
@@ -215,11 +242,36 @@ impl Node {
         sodium_ctx.to_be_updated_set.contains(self)
     }
 
+    pub fn id(&self) -> u32 {
+        let data = unsafe { &*(*self.data).get() };
+        data.id
+    }
+
+    pub fn desc(&self) -> String {
+        self.desc_str().to_string()
+    }
+
+    fn desc_str(&self) -> &str {
+        self.data.desc().unwrap_or("")
+    }
+
+    pub fn dependencies(&self) -> Vec<Node> {
+        let data = unsafe { &*(*self.data).get() };
+        data.dependencies.clone()
+    }
+
+    pub fn dependency_ids(&self) -> Vec<u32> {
+        let data = unsafe { &*(*self.data).get() };
+        data.dependencies.iter().map(|dependency| dependency.id()).collect()
+    }
+
     pub fn rank(&self) -> u32 {
         let data = unsafe { &*(*self.data).get() };
         data.rank.clone()
     }
 
+    /// Raises the rank of this node above `rank`, and those of its dependents
+    /// above it in turn, for a dependency added after the node was created.
     pub fn ensure_bigger_than(&self, rank: u32) {
         let sodium_ctx = self.sodium_ctx();
         let sodium_ctx = &sodium_ctx;
@@ -233,7 +285,7 @@ impl Node {
             return;
         }
         visited.insert(self_.id);
-        if self_.rank <= rank {
+        if self_.rank > rank {
             return
         }
         let rank2 = rank + 1;
@@ -264,6 +316,7 @@ impl Node {
 
 impl Drop for NodeData {
     fn drop(&mut self) {
+        self.sodium_ctx.unregister_node(self.id);
         self.sodium_ctx.dec_node_count();
     }
 }
@@ -324,7 +377,7 @@ impl Ord for Node {
     fn cmp(&self, other: &Node) -> Ordering {
         let self_ = unsafe { &*(*self).data.get() };
         let other = unsafe { &*(*other).data.get() };
-        self_.rank.cmp(&other.rank).reverse()
+        self_.rank.cmp(&other.rank).then(self_.id.cmp(&other.id)).reverse()
     }
 }
 
@@ -338,7 +391,9 @@ impl Eq for Node {}
 
 impl PartialEq for Node {
     fn eq(&self, other: &Node) -> bool {
-        self.cmp(other) == Ordering::Equal
+        let self_ = unsafe { &*(*self.data).get() };
+        let other = unsafe { &*(*other.data).get() };
+        self_.id == other.id
     }
 }
 
@@ -0,0 +1,77 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub struct PendingQueue {
+    pub items: VecDeque<PendingItem>,
+    pub notifier_op: Option<Arc<dyn Fn() + Send + Sync>>
+}
+
+pub type PendingSend = Rc<dyn Fn(Box<dyn Any + Send>)>;
+
+pub enum PendingItem {
+    Send(u32, Box<dyn Any + Send>),
+    Release(u32)
+}
+
+impl PendingQueue {
+    pub fn new() -> PendingQueue {
+        PendingQueue {
+            items: VecDeque::new(),
+            notifier_op: None
+        }
+    }
+}
+
+pub fn push_pending(queue: &Mutex<PendingQueue>, item: PendingItem) {
+    let notifier_op;
+    {
+        let mut queue = queue.lock().unwrap();
+        queue.items.push_back(item);
+        notifier_op = queue.notifier_op.clone();
+    }
+    if let Some(notifier) = notifier_op {
+        notifier();
+    }
+}
+
+pub struct SinkSender<A> {
+    handle: Arc<SinkSenderHandle>,
+    phantom: PhantomData<fn(A)>
+}
+
+struct SinkSenderHandle {
+    id: u32,
+    queue: Arc<Mutex<PendingQueue>>
+}
+
+impl<A: Send + 'static> SinkSender<A> {
+    pub fn new(id: u32, queue: Arc<Mutex<PendingQueue>>) -> SinkSender<A> {
+        SinkSender {
+            handle: Arc::new(SinkSenderHandle { id, queue }),
+            phantom: PhantomData
+        }
+    }
+
+    pub fn send(&self, a: A) {
+        push_pending(&self.handle.queue, PendingItem::Send(self.handle.id, Box::new(a)));
+    }
+}
+
+impl Drop for SinkSenderHandle {
+    fn drop(&mut self) {
+        push_pending(&self.queue, PendingItem::Release(self.id));
+    }
+}
+
+impl<A> Clone for SinkSender<A> {
+    fn clone(&self) -> Self {
+        SinkSender {
+            handle: self.handle.clone(),
+            phantom: PhantomData
+        }
+    }
+}
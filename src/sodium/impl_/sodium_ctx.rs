@@ -1,15 +1,40 @@
 use sodium::gc::Finalize;
 use sodium::gc::GcCtx;
+use sodium::gc::GcDep;
 use sodium::gc::Trace;
+use sodium::impl_::GraphDump;
+use sodium::impl_::HookKind;
 use sodium::impl_::IsLambda0;
+use sodium::impl_::LeakReport;
+use sodium::impl_::LeakedNode;
 use sodium::impl_::MemoLazy;
 use sodium::impl_::Node;
+use sodium::impl_::NodeDump;
+use sodium::impl_::PendingItem;
+use sodium::impl_::PendingQueue;
+use sodium::impl_::PendingSend;
+use sodium::impl_::Retention;
+use sodium::impl_::SinkSender;
+use sodium::impl_::SodiumError;
+use sodium::impl_::TransactionObserver;
+use sodium::impl_::WeakNode;
+use std::any::Any;
 use std::cell::UnsafeCell;
 use std::collections::BinaryHeap;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::mem::swap;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::rc::Rc;
 use std::rc::Weak;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic;
+use std::sync::atomic::AtomicU64;
+
+static NEXT_CTX_ID: AtomicU64 = AtomicU64::new(1);
 
 pub struct SodiumCtx {
     pub data: Rc<UnsafeCell<SodiumCtxData>>
@@ -19,39 +44,91 @@ pub struct WeakSodiumCtx {
     pub data: Weak<UnsafeCell<SodiumCtxData>>
 }
 
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum CallbackSendMode {
+    Panic,
+    Defer
+}
+
 pub struct SodiumCtxData {
+    pub id: u64,
     pub gc_ctx: GcCtx,
     pub next_id: u32,
     pub transaction_depth: u32,
+    pub transaction_seq: u64,
+    pub propergate_depth: u32,
     pub callback_depth: u32,
     pub to_be_updated: BinaryHeap<Node>,
     pub to_be_updated_set: HashSet<Node>,
     pub resort_required: bool,
     pub pre_trans: Vec<Box<FnMut()>>,
     pub post_trans: Vec<Box<FnMut()>>,
+    pub after_trans: Vec<Box<dyn FnOnce()>>,
+    pub deferred_sends: VecDeque<Box<dyn FnOnce()>>,
+    pub callback_send_mode: CallbackSendMode,
     pub node_count: u32,
-    pub keep_alive: HashSet<Node>
+    pub keep_alive: HashSet<Node>,
+    pub track_nodes: bool,
+    pub nodes: HashMap<u32,WeakNode>,
+    pub observers: Vec<(u32,Box<dyn TransactionObserver>)>,
+    pub pending_queue: Arc<Mutex<PendingQueue>>,
+    pub pending_sinks: HashMap<u32,PendingSend>,
+    pub new_loops: Vec<(u32,Box<dyn Fn()->bool>)>,
+    pub unlooped_cell_loops: HashSet<u32>
 }
 
 impl SodiumCtx {
     pub fn new() -> SodiumCtx {
         SodiumCtx {
             data: Rc::new(UnsafeCell::new(SodiumCtxData {
+                id: NEXT_CTX_ID.fetch_add(1, atomic::Ordering::Relaxed),
                 gc_ctx: GcCtx::new(),
                 next_id: 0,
                 transaction_depth: 0,
+                transaction_seq: 0,
+                propergate_depth: 0,
                 callback_depth: 0,
                 to_be_updated: BinaryHeap::new(),
                 to_be_updated_set: HashSet::new(),
                 resort_required: false,
                 pre_trans: Vec::new(),
                 post_trans: Vec::new(),
+                after_trans: Vec::new(),
+                deferred_sends: VecDeque::new(),
+                callback_send_mode: CallbackSendMode::Panic,
                 node_count: 0,
-                keep_alive: HashSet::new()
+                keep_alive: HashSet::new(),
+                track_nodes: false,
+                nodes: HashMap::new(),
+                observers: Vec::new(),
+                pending_queue: Arc::new(Mutex::new(PendingQueue::new())),
+                pending_sinks: HashMap::new(),
+                new_loops: Vec::new(),
+                unlooped_cell_loops: HashSet::new()
             }))
         }
     }
 
+    /// Unique among the contexts created by this process.
+    pub fn id(&self) -> u64 {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.id
+    }
+
+    /// Panics in debug builds when node belongs to another context. `op`
+    /// names the combinator for the message.
+    pub fn assert_same_ctx(&self, node: &Node, op: &str) {
+        if cfg!(debug_assertions) {
+            let node_ctx_id = node.sodium_ctx().id();
+            if node_ctx_id != self.id() {
+                panic!(
+                    "{}: \"{}\" belongs to SodiumCtx {}, but is being combined with objects from SodiumCtx {}. Objects from different contexts can not be mixed.",
+                    op, node.desc(), node_ctx_id, self.id()
+                );
+            }
+        }
+    }
+
     pub fn gc_ctx(&self) -> GcCtx {
         let self_ = unsafe { &*(*self.data).get() };
         self_.gc_ctx.clone()
@@ -86,6 +163,134 @@ impl SodiumCtx {
         self_.keep_alive.remove(node);
     }
 
+    /// Whether nodes created from now on are recorded for `dump_graph` and
+    /// `leak_report`. Off by default, as it costs a map entry per node.
+    pub fn set_node_tracking(&self, enabled: bool) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.track_nodes = enabled;
+    }
+
+    pub fn node_tracking(&self) -> bool {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.track_nodes
+    }
+
+    pub fn register_node(&self, node: &Node) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        if self_.track_nodes {
+            self_.nodes.insert(node.id(), node.downgrade());
+        }
+    }
+
+    pub fn unregister_node(&self, id: u32) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        if !self_.nodes.is_empty() {
+            self_.nodes.remove(&id);
+        }
+    }
+
+    /// Snapshot of every live node created while node tracking was on,
+    /// ordered by id.
+    pub fn dump_graph(&self) -> GraphDump {
+        let self_ = unsafe { &*(*self.data).get() };
+        let mut nodes: Vec<Node> = self_.nodes.values().filter_map(|node| node.upgrade()).collect();
+        nodes.sort_by_key(|node| node.id());
+        GraphDump {
+            nodes: nodes.iter().map(|node| NodeDump {
+                id: node.id(),
+                desc: node.desc(),
+                rank: node.rank(),
+                dependencies: node.dependency_ids(),
+                kept_alive: self_.keep_alive.contains(node)
+            }).collect()
+        }
+    }
+
+    /// What keeps each live node alive, ordered by id. Works on the GC's
+    /// trace graph of the live nodes: a value referenced more often than the
+    /// graph itself references it is held from outside, and the shortest path
+    /// from such a value, preferring listeners, is reported for each node.
+    /// Only nodes created while node tracking was on are listed.
+    pub fn leak_report(&self) -> LeakReport {
+        let self_ = unsafe { &*(*self.data).get() };
+        let mut nodes: Vec<Node> = self_.nodes.values().filter_map(|node| node.upgrade()).collect();
+        nodes.sort_by_key(|node| node.id());
+        let gc_deps: Vec<GcDep> = nodes.iter().map(|node| node.to_dep().gc_dep).collect();
+        let graph = self_.gc_ctx.trace_graph(&gc_deps);
+        let node_by_gc: HashMap<usize,&Node> = gc_deps.iter().map(|dep| dep.id()).zip(&nodes).collect();
+        let kept_alive: HashSet<usize> = self_.keep_alive.iter().map(|node| node.to_dep().gc_dep.id()).collect();
+        let mut incoming: HashMap<usize,i32> = HashMap::new();
+        for gc in &graph {
+            for edge in &gc.edges {
+                *incoming.entry(*edge).or_insert(0) += 1;
+            }
+        }
+        let mut listener_roots = Vec::new();
+        let mut held_roots = Vec::new();
+        for gc in &graph {
+            let mut external = gc.strong - incoming.get(&gc.id).cloned().unwrap_or(0);
+            if node_by_gc.contains_key(&gc.id) {
+                // The upgrade made for this report.
+                external -= 1;
+            }
+            if kept_alive.contains(&gc.id) {
+                listener_roots.push(gc.id);
+            } else if external > 0 {
+                held_roots.push(gc.id);
+            }
+        }
+        let edges: HashMap<usize,&Vec<usize>> = graph.iter().map(|gc| (gc.id, &gc.edges)).collect();
+        let mut parents: HashMap<usize,Option<usize>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for root in listener_roots.iter().chain(&held_roots) {
+            parents.insert(*root, None);
+            queue.push_back(*root);
+        }
+        while let Some(id) = queue.pop_front() {
+            for edge in edges[&id].iter() {
+                if !parents.contains_key(edge) {
+                    parents.insert(*edge, Some(id));
+                    queue.push_back(*edge);
+                }
+            }
+        }
+        let descs: HashMap<usize,String> =
+            graph.iter()
+                .map(|gc| (gc.id, match node_by_gc.get(&gc.id) {
+                    Some(node) => format!("{} {}", node.id(), node.desc()),
+                    None => gc.desc.clone().unwrap_or_else(|| String::from("?"))
+                }))
+                .collect();
+        LeakReport {
+            nodes: nodes.iter().zip(&gc_deps).map(|(node, dep)| {
+                let retention =
+                    if parents.contains_key(&dep.id()) {
+                        let mut path = Vec::new();
+                        let mut at = Some(dep.id());
+                        let mut root = dep.id();
+                        while let Some(id) = at {
+                            path.push(descs[&id].clone());
+                            root = id;
+                            at = parents[&id];
+                        }
+                        path.reverse();
+                        if kept_alive.contains(&root) {
+                            Retention::Listener { path }
+                        } else {
+                            Retention::Held { path }
+                        }
+                    } else {
+                        Retention::Cycle
+                    };
+                LeakedNode {
+                    id: node.id(),
+                    desc: node.desc(),
+                    retention
+                }
+            }).collect()
+        }
+    }
+
     pub fn inc_node_count(&self) {
         let self_ = unsafe { &mut *(*self.data).get() };
         self_.node_count = self_.node_count + 1;
@@ -111,11 +316,117 @@ impl SodiumCtx {
         self_.callback_depth = self_.callback_depth - 1;
     }
 
+    /// Counts outermost transactions, so sends made in the same one share a
+    /// number.
+    pub fn transaction_seq(&self) -> u64 {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.transaction_seq
+    }
+
+    /// True while a transaction is being propagated, including its pre, post
+    /// and after_trans hooks.
+    pub fn is_propergating(&self) -> bool {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.propergate_depth > 0
+    }
+
     pub fn callback_depth(&self) -> u32 {
         let self_ = unsafe { &*(*self.data).get() };
         self_.callback_depth
     }
 
+    pub fn callback_send_mode(&self) -> CallbackSendMode {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.callback_send_mode
+    }
+
+    pub fn set_callback_send_mode(&self, mode: CallbackSendMode) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.callback_send_mode = mode;
+    }
+
+    pub fn new_sink_sender<A: Send + 'static, F: Fn(A) + 'static>(&self, send: F) -> SinkSender<A> {
+        let id = self.new_id();
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.pending_sinks.insert(id, Rc::new(move |a: Box<dyn Any + Send>| {
+            if let Ok(a) = a.downcast::<A>() {
+                send(*a);
+            }
+        }));
+        SinkSender::new(id, self_.pending_queue.clone())
+    }
+
+    pub fn set_pending_notifier<F: Fn() + Send + Sync + 'static>(&self, notifier: F) {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.pending_queue.lock().unwrap().notifier_op = Some(Arc::new(notifier));
+    }
+
+    pub fn run_pending(&self) -> usize {
+        if self.callback_depth() > 0 {
+            panic!("SodiumCtx::run_pending can not be called from a sodium callback, consider using SodiumCtx::post to run after the end of transaction.")
+        }
+        let mut sent = 0;
+        loop {
+            let item_op = {
+                let self_ = unsafe { &*(*self.data).get() };
+                let mut pending_queue = self_.pending_queue.lock().unwrap();
+                pending_queue.items.pop_front()
+            };
+            match item_op {
+                Some(PendingItem::Send(id, a)) => {
+                    // Left in the map while it runs, so a panicking send
+                    // does not unregister the sink.
+                    let send_op = {
+                        let self_ = unsafe { &*(*self.data).get() };
+                        self_.pending_sinks.get(&id).cloned()
+                    };
+                    if let Some(send) = send_op {
+                        send(a);
+                        sent += 1;
+                    }
+                },
+                Some(PendingItem::Release(id)) => {
+                    let self_ = unsafe { &mut *(*self.data).get() };
+                    let send_op = self_.pending_sinks.remove(&id);
+                    drop(send_op);
+                },
+                None => break
+            }
+        }
+        sent
+    }
+
+    pub fn add_transaction_observer<OBSERVER: TransactionObserver + 'static>(&self, observer: OBSERVER) -> u32 {
+        let id = self.new_id();
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.observers.push((id, Box::new(observer)));
+        id
+    }
+
+    pub fn remove_transaction_observer(&self, id: u32) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.observers.retain(|&(id2, _)| id2 != id);
+    }
+
+    pub fn has_transaction_observers(&self) -> bool {
+        let self_ = unsafe { &*(*self.data).get() };
+        !self_.observers.is_empty()
+    }
+
+    pub fn notify_observers<F: FnMut(&mut dyn TransactionObserver)>(&self, mut f: F) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        if self_.observers.is_empty() {
+            return;
+        }
+        let mut observers = Vec::new();
+        swap(&mut self_.observers, &mut observers);
+        for &mut (_, ref mut observer) in &mut observers {
+            f(observer.as_mut());
+        }
+        observers.append(&mut self_.observers);
+        self_.observers = observers;
+    }
+
     pub fn pre<F: FnMut() + 'static>(&self, f: F) {
         self.transaction(|| {
             let self_ = unsafe { &mut *(*self.data).get() };
@@ -130,15 +441,157 @@ impl SodiumCtx {
         });
     }
 
+    pub fn after_trans<F: FnOnce() + 'static>(&self, f: F) {
+        self.transaction(|| {
+            let self_ = unsafe { &mut *(*self.data).get() };
+            self_.after_trans.push(Box::new(f));
+        });
+    }
+
+    /// Queues a send made from a callback under `CallbackSendMode::Defer`.
+    /// Each runs in its own transaction once the outermost propagation has
+    /// finished, one after another, so a chain of deferred sends does not
+    /// grow the stack.
+    pub fn defer_send<F: FnOnce() + 'static>(&self, f: F) {
+        self.transaction(|| {
+            let self_ = unsafe { &mut *(*self.data).get() };
+            self_.deferred_sends.push_back(Box::new(f));
+        });
+    }
+
+    /// Runs code in a transaction. If code or the propagation panics, the
+    /// outermost transaction resets the context before the panic continues,
+    /// see `recover_from_panic`.
     pub fn transaction<A,CODE:FnOnce()->A>(&self, code: CODE)->A {
+        self._transaction(code).0
+    }
+
+    /// Like `transaction`, but a panic is returned as an error instead. When
+    /// this is the outermost transaction, loops created in it that were not
+    /// looped are also reported, after the transaction has taken effect.
+    pub fn try_transaction<A,CODE:FnOnce()->A>(&self, code: CODE) -> Result<A,SodiumError> {
+        let (result, unlooped) =
+            panic::catch_unwind(AssertUnwindSafe(|| self._transaction(code)))
+                .map_err(|payload| SodiumError::from_panic(&*payload))?;
+        if !unlooped.is_empty() {
+            return Err(SodiumError::Unlooped(unlooped));
+        }
+        Ok(result)
+    }
+
+    fn _transaction<A,CODE:FnOnce()->A>(&self, code: CODE) -> (A,Vec<u32>) {
         let self_ = unsafe { &mut *(*self.data).get() };
+        let outermost = self_.transaction_depth == 0;
+        if outermost {
+            self_.transaction_seq += 1;
+            self_.gc_ctx.transaction_start();
+            self.notify_observers(|observer| observer.transaction_start());
+        }
         self_.transaction_depth = self_.transaction_depth + 1;
-        let result = code();
+        let result_op = panic::catch_unwind(AssertUnwindSafe(code));
         self_.transaction_depth = self_.transaction_depth - 1;
-        if self_.transaction_depth == 0 {
-            self.propergate();
+        let result = match result_op {
+            Ok(result) => result,
+            Err(payload) => {
+                if outermost {
+                    self.recover_from_panic();
+                }
+                panic::resume_unwind(payload)
+            }
+        };
+        let mut unlooped = Vec::new();
+        if outermost {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.propergate())) {
+                self.recover_from_panic();
+                panic::resume_unwind(payload);
+            }
+            // Loops can also be created while propagating, e.g. in the
+            // function given to Cell::switch_s, so check after it.
+            let mut new_loops = Vec::new();
+            swap(&mut self_.new_loops, &mut new_loops);
+            for (id, is_looped) in new_loops {
+                if !is_looped() {
+                    unlooped.push(id);
+                }
+            }
+        }
+        (result, unlooped)
+    }
+
+    /// Records a `CellLoop` or `StreamLoop` created inside a transaction, so
+    /// `try_transaction` can report it if it is not looped by the end.
+    pub fn register_loop<CHECK: Fn()->bool + 'static>(&self, id: u32, is_looped: CHECK) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        if self_.transaction_depth > 0 || self_.propergate_depth > 0 {
+            self_.new_loops.push((id, Box::new(is_looped)));
+        }
+    }
+
+    pub fn add_unlooped_cell_loop(&self, id: u32) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.unlooped_cell_loops.insert(id);
+    }
+
+    pub fn remove_unlooped_cell_loop(&self, id: u32) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.unlooped_cell_loops.remove(&id);
+    }
+
+    /// Whether sampling the node's cell would sample a `CellLoop` that has
+    /// not been looped yet.
+    pub fn depends_on_unlooped_cell_loop(&self, node: &Node) -> bool {
+        let self_ = unsafe { &*(*self.data).get() };
+        if self_.unlooped_cell_loops.is_empty() {
+            return false;
+        }
+        let mut visited = HashSet::new();
+        let mut stack = vec![node.clone()];
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node.id()) {
+                continue;
+            }
+            if self_.unlooped_cell_loops.contains(&node.id()) {
+                return true;
+            }
+            stack.extend(node.dependencies());
         }
-        result
+        false
+    }
+
+    /// Leaves the context as if the failed transaction had finished: the
+    /// update queue is emptied and the depths are reset. Queued `post`
+    /// closures still run, as they reset per transaction state such as stream
+    /// firings, while `pre` and `after_trans` closures and deferred sends are
+    /// dropped.
+    ///
+    /// This is not a rollback. The same `post` closures commit the new values
+    /// of the cells updated before the panic, so when propagation panics part
+    /// way through, those cells keep their new values while the rest keep
+    /// their old ones.
+    fn recover_from_panic(&self) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.transaction_depth = 0;
+        self_.propergate_depth = 0;
+        self_.callback_depth = 0;
+        self_.to_be_updated.clear();
+        self_.to_be_updated_set.clear();
+        self_.resort_required = false;
+        self_.pre_trans.clear();
+        self_.after_trans.clear();
+        self_.deferred_sends.clear();
+        self_.new_loops.clear();
+        loop {
+            let mut post_trans = Vec::new();
+            swap(&mut self_.post_trans, &mut post_trans);
+            if post_trans.is_empty() {
+                break;
+            }
+            for mut f in post_trans {
+                let _ = panic::catch_unwind(AssertUnwindSafe(&mut f));
+            }
+        }
+        self_.gc_ctx.transaction_end();
+        self.notify_observers(|observer| observer.transaction_end());
     }
 
     pub fn schedule_update_sort(&self) {
@@ -148,6 +601,7 @@ impl SodiumCtx {
 
     fn propergate(&self) {
         let self_ = unsafe { &mut *(*self.data).get() };
+        self_.propergate_depth += 1;
         if self_.resort_required {
             self_.to_be_updated.clear();
             for node in &self_.to_be_updated_set {
@@ -159,7 +613,9 @@ impl SodiumCtx {
             let mut pre_trans = Vec::new();
             swap(&mut self_.pre_trans, &mut pre_trans);
             for mut f in pre_trans {
+                self.notify_observers(|observer| observer.hook_start(HookKind::Pre));
                 f();
+                self.notify_observers(|observer| observer.hook_end(HookKind::Pre));
             }
             if self_.pre_trans.is_empty() {
                 break;
@@ -171,7 +627,15 @@ impl SodiumCtx {
             match node_op {
                 Some(node) => {
                     self_.to_be_updated_set.remove(&node);
+                    let observed = self.has_transaction_observers();
+                    let info_op = if observed { Some((node.id(), node.desc(), node.rank())) } else { None };
+                    if let Some((id, ref desc, rank)) = info_op {
+                        self.notify_observers(|observer| observer.node_update_start(id, desc, rank));
+                    }
                     let mark_dependents_dirty = node.update();
+                    if let Some((id, ref desc, rank)) = info_op {
+                        self.notify_observers(|observer| observer.node_update_end(id, desc, rank, mark_dependents_dirty));
+                    }
                     if mark_dependents_dirty {
                         node.mark_dependents_dirty();
                     }
@@ -184,12 +648,34 @@ impl SodiumCtx {
             let mut post_trans = Vec::new();
             swap(&mut self_.post_trans, &mut post_trans);
             for mut f in post_trans {
+                self.notify_observers(|observer| observer.hook_start(HookKind::Post));
                 f();
+                self.notify_observers(|observer| observer.hook_end(HookKind::Post));
             }
             if self_.post_trans.is_empty() {
                 break;
             }
         }
+        self_.gc_ctx.transaction_end();
+        loop {
+            let mut after_trans = Vec::new();
+            swap(&mut self_.after_trans, &mut after_trans);
+            if after_trans.is_empty() {
+                break;
+            }
+            for f in after_trans {
+                self.notify_observers(|observer| observer.hook_start(HookKind::AfterTrans));
+                f();
+                self.notify_observers(|observer| observer.hook_end(HookKind::AfterTrans));
+            }
+        }
+        if self_.propergate_depth == 1 {
+            while let Some(send) = self_.deferred_sends.pop_front() {
+                send();
+            }
+        }
+        self_.propergate_depth -= 1;
+        self.notify_observers(|observer| observer.transaction_end());
     }
 }
 
@@ -0,0 +1,48 @@
+use std::any::Any;
+use std::error;
+use std::fmt;
+
+/// The ways a transaction run with `SodiumCtx::try_transaction` can fail.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum SodiumError {
+    /// Code run inside the transaction panicked. The context has been reset
+    /// and can still be used.
+    Panicked(String),
+    /// `loop_` was called on a loop that had already been looped.
+    AlreadyLooped,
+    /// A `CellLoop`, or a cell built from one, was sampled before the loop
+    /// was looped.
+    SampledBeforeLooped,
+    /// Loops created in the transaction were still not looped when it ended.
+    /// Holds the ids of their nodes, as used by `SodiumCtx::dump_graph`.
+    Unlooped(Vec<u32>)
+}
+
+pub const SAMPLED_BEFORE_LOOPED: &str = "CellLoop sampled before looped.";
+
+impl SodiumError {
+    pub fn from_panic(payload: &(dyn Any + Send)) -> SodiumError {
+        let msg =
+            if let Some(msg) = payload.downcast_ref::<&str>() {
+                msg.to_string()
+            } else if let Some(msg) = payload.downcast_ref::<String>() {
+                msg.clone()
+            } else {
+                String::from("unknown panic")
+            };
+        SodiumError::Panicked(msg)
+    }
+}
+
+impl fmt::Display for SodiumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SodiumError::Panicked(ref msg) => write!(f, "transaction panicked: {}", msg),
+            SodiumError::AlreadyLooped => write!(f, "looped more than once"),
+            SodiumError::SampledBeforeLooped => write!(f, "{}", SAMPLED_BEFORE_LOOPED),
+            SodiumError::Unlooped(ref ids) => write!(f, "loops were not looped by the end of the transaction, node ids: {:?}", ids)
+        }
+    }
+}
+
+impl error::Error for SodiumError {}
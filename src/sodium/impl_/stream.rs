@@ -1,4 +1,5 @@
 use sodium::impl_::Cell;
+use sodium::impl_::CellLoop;
 use sodium::impl_::Dep;
 use sodium::impl_::IsLambda0;
 use sodium::impl_::IsLambdaMut0;
@@ -19,8 +20,11 @@ use sodium::gc::Gc;
 use sodium::gc::GcDep;
 use sodium::gc::Trace;
 use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
+type MergeKeyedFired<K,A> = Gc<UnsafeCell<Vec<(K,MemoLazy<A>)>>>;
+
 pub struct Stream<A> {
     pub data: Gc<UnsafeCell<StreamData<A>>>
 }
@@ -77,6 +81,26 @@ impl<A: Clone + Trace + Finalize + 'static> Stream<Option<A>> {
     }
 }
 
+impl<A: Clone + Trace + Finalize + PartialEq + 'static> Stream<A> {
+    /// Drops events equal to the last one passed on.
+    pub fn calm(&self) -> Stream<A> {
+        let sodium_ctx = self._node().sodium_ctx();
+        self.calm_lazy(sodium_ctx.new_lazy(|| None))
+    }
+
+    /// Like `calm`, but the first event is also dropped if it is equal to
+    /// `init`.
+    pub fn calm_lazy(&self, init: MemoLazy<Option<A>>) -> Stream<A> {
+        self.collect_lazy(init, |a: &A, last: &Option<A>| {
+            if last.as_ref() == Some(a) {
+                (None, last.clone())
+            } else {
+                (Some(a.clone()), Some(a.clone()))
+            }
+        }).filter_option()
+    }
+}
+
 impl<A: Clone + Trace + Finalize + 'static> Stream<A> {
     pub fn new(sodium_ctx: &SodiumCtx) -> Stream<A> {
         Stream::_new(
@@ -181,6 +205,50 @@ impl<A: Clone + Trace + Finalize + 'static> Stream<A> {
         )
     }
 
+    fn _assert_same_ctx(&self, nodes: &[&Node], op: &str) {
+        let sodium_ctx = self._node().sodium_ctx();
+        for node in nodes {
+            sodium_ctx.assert_same_ctx(node, op);
+        }
+    }
+
+    // Takes the cells' value thunks when the stream fires. Sampling them any
+    // later could see the values this transaction gives them.
+    fn _snapshot<T,C,SAMPLE,FN>(&self, sample: SAMPLE, f: FN, deps: Vec<Dep>) -> Stream<C>
+        where T: Trace + 'static,
+              C: Clone + Trace + Finalize + 'static,
+              SAMPLE: Fn()->T + 'static,
+              FN: Fn(&A,&T)->C + 'static
+    {
+        let sodium_ctx = self._node().sodium_ctx();
+        let sodium_ctx = &sodium_ctx;
+        let self_ = self.clone();
+        let f = Rc::new(f);
+        let mut update_deps = deps;
+        update_deps.push(self.to_dep());
+        let sodium_ctx2 = sodium_ctx.clone();
+        Stream::_new(
+            sodium_ctx,
+            Lambda::new(
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    self_.peek_value().map(|thunk| {
+                        let f = f.clone();
+                        let t = sample();
+                        let mut deps = Vec::new();
+                        thunk.trace(&mut |gc_dep| deps.push(Dep { gc_dep: gc_dep.clone() }));
+                        t.trace(&mut |gc_dep| deps.push(Dep { gc_dep: gc_dep.clone() }));
+                        sodium_ctx.new_lazy(Lambda::new(move || f(thunk.get(), &t), deps))
+                    })
+                },
+                update_deps
+            ),
+            vec![self._node().clone()],
+            || {},
+            "Stream::snapshot"
+        )
+    }
+
     pub fn hold(&self, a: A) -> Cell<A> {
         let sodium_ctx = self._node().sodium_ctx();
         let sodium_ctx = &sodium_ctx;
@@ -304,7 +372,9 @@ impl<A: Clone + Trace + Finalize + 'static> Stream<A> {
     }
 
     pub fn gate(&self, ca: Cell<bool>) -> Stream<A> {
-        self.filter(move |_: &A| ca.sample_no_trans())
+        self._assert_same_ctx(&[ca._node()], "Stream::gate");
+        let deps = vec![ca.to_dep()];
+        self.filter(Lambda::new(move |_: &A| ca.sample_no_trans(), deps))
     }
 
     pub fn collect_lazy<B,S,F>(&self, init_state: MemoLazy<S>, f: F) -> Stream<B>
@@ -405,48 +475,331 @@ impl<A: Clone + Trace + Finalize + 'static> Stream<A> {
     }
 
     pub fn snapshot<B>(&self, cb: Cell<B>) -> Stream<B> where B: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&[cb._node()], "Stream::snapshot");
         let deps = vec![cb.to_dep()];
-        self.map(Lambda::new(move |_a: &A| cb.sample_no_trans(), deps))
+        self._snapshot(move || cb._value_thunk(), |_a: &A, b: &MemoLazy<B>| b.get().clone(), deps)
     }
 
     pub fn snapshot2<B,C,FN:IsLambda2<A,B,C> + 'static>(&self, cb: Cell<B>, f: FN) -> Stream<C> where B: Trace + Finalize + Clone + 'static, C: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&[cb._node()], "Stream::snapshot");
         let mut deps = f.deps();
         deps.push(cb.to_dep());
-        self.map(Lambda::new(move |a: &A| f.apply(a, &cb.sample_no_trans()), deps))
+        self._snapshot(move || cb._value_thunk(), move |a: &A, b: &MemoLazy<B>| f.apply(a, b.get()), deps)
     }
 
     pub fn snapshot3<B,C,D,FN:IsLambda3<A,B,C,D> + 'static>(&self, cb: Cell<B>, cc: Cell<C>, f: FN) -> Stream<D> where B: Trace + Finalize + Clone + 'static, C: Trace + Finalize + Clone + 'static, D: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&[cb._node(), cc._node()], "Stream::snapshot");
         let mut deps = f.deps();
         deps.push(cb.to_dep());
         deps.push(cc.to_dep());
-        self.map(Lambda::new(move |a: &A| f.apply(a, &cb.sample_no_trans(), &cc.sample_no_trans()), deps))
+        self._snapshot(
+            move || (cb._value_thunk(), cc._value_thunk()),
+            move |a: &A, t| f.apply(a, t.0.get(), t.1.get()),
+            deps
+        )
     }
 
     pub fn snapshot4<B,C,D,E,FN:IsLambda4<A,B,C,D,E> + 'static>(&self, cb: Cell<B>, cc: Cell<C>, cd: Cell<D>, f: FN) -> Stream<E> where B: Trace + Finalize + Clone + 'static, C: Trace + Finalize + Clone + 'static, D: Trace + Finalize + Clone + 'static, E: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&[cb._node(), cc._node(), cd._node()], "Stream::snapshot");
         let mut deps = f.deps();
         deps.push(cb.to_dep());
         deps.push(cc.to_dep());
         deps.push(cd.to_dep());
-        self.map(Lambda::new(move |a: &A| f.apply(a, &cb.sample_no_trans(), &cc.sample_no_trans(), &cd.sample_no_trans()), deps))
+        self._snapshot(
+            move || (cb._value_thunk(), cc._value_thunk(), cd._value_thunk()),
+            move |a: &A, t| f.apply(a, t.0.get(), t.1.get(), t.2.get()),
+            deps
+        )
     }
 
     pub fn snapshot5<B,C,D,E,F,FN:IsLambda5<A,B,C,D,E,F> + 'static>(&self, cb: Cell<B>, cc: Cell<C>, cd: Cell<D>, ce: Cell<E>, f: FN) -> Stream<F> where B: Trace + Finalize + Clone + 'static, C: Trace + Finalize + Clone + 'static, D: Trace + Finalize + Clone + 'static, E: Trace + Finalize + Clone + 'static, E: Trace + Finalize + Clone + 'static, F: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&[cb._node(), cc._node(), cd._node(), ce._node()], "Stream::snapshot");
         let mut deps = f.deps();
         deps.push(cb.to_dep());
         deps.push(cc.to_dep());
         deps.push(cd.to_dep());
         deps.push(ce.to_dep());
-        self.map(Lambda::new(move |a: &A| f.apply(a, &cb.sample_no_trans(), &cc.sample_no_trans(), &cd.sample_no_trans(), &ce.sample_no_trans()), deps))
+        self._snapshot(
+            move || (cb._value_thunk(), cc._value_thunk(), cd._value_thunk(), ce._value_thunk()),
+            move |a: &A, t| f.apply(a, t.0.get(), t.1.get(), t.2.get(), t.3.get()),
+            deps
+        )
     }
 
     pub fn snapshot6<B,C,D,E,F,G,FN:IsLambda6<A,B,C,D,E,F,G> + 'static>(&self, cb: Cell<B>, cc: Cell<C>, cd: Cell<D>, ce: Cell<E>, cf: Cell<F>, f: FN) -> Stream<G> where B: Trace + Finalize + Clone + 'static, C: Trace + Finalize + Clone + 'static, D: Trace + Finalize + Clone + 'static, E: Trace + Finalize + Clone + 'static, E: Trace + Finalize + Clone + 'static, F: Trace + Finalize + Clone + 'static, G: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&[cb._node(), cc._node(), cd._node(), ce._node(), cf._node()], "Stream::snapshot");
         let mut deps = f.deps();
         deps.push(cb.to_dep());
         deps.push(cc.to_dep());
         deps.push(cd.to_dep());
         deps.push(ce.to_dep());
         deps.push(cf.to_dep());
-        self.map(Lambda::new(move |a: &A| f.apply(a, &cb.sample_no_trans(), &cc.sample_no_trans(), &cd.sample_no_trans(), &ce.sample_no_trans(), &cf.sample_no_trans()), deps))
+        self._snapshot(
+            move || (cb._value_thunk(), cc._value_thunk(), cd._value_thunk(), ce._value_thunk(), cf._value_thunk()),
+            move |a: &A, t| f.apply(a, t.0.get(), t.1.get(), t.2.get(), t.3.get(), t.4.get()),
+            deps
+        )
+    }
+
+    pub fn snapshot_all<B>(&self, cells: Vec<Cell<B>>) -> Stream<Vec<B>> where B: Trace + Finalize + Clone + 'static {
+        self._assert_same_ctx(&cells.iter().map(|c| c._node()).collect::<Vec<_>>(), "Stream::snapshot_all");
+        let deps = cells.iter().map(|c| c.to_dep()).collect();
+        self._snapshot(
+            move || cells.iter().map(|c| c._value_thunk()).collect::<Vec<_>>(),
+            |_a: &A, thunks: &Vec<MemoLazy<B>>| thunks.iter().map(|thunk| thunk.get().clone()).collect(),
+            deps
+        )
+    }
+
+    pub fn switch_map<B,F>(&self, f: F) -> Stream<B>
+        where B: Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,Stream<B>> + 'static
+    {
+        let sodium_ctx = self._node().sodium_ctx();
+        sodium_ctx.transaction(|| {
+            Cell::switch_s(self.map(f).hold(Stream::new(&sodium_ctx)))
+        })
+    }
+
+    /// Like `switch_map`, but events that arrive while the current inner
+    /// stream is active are ignored. Each `Some` of the inner stream is
+    /// passed on, and a `None` ends it, after which the next event starts a
+    /// new one.
+    pub fn exhaust_map<B,F>(&self, f: F) -> Stream<B>
+        where B: Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,Stream<Option<B>>> + 'static
+    {
+        let sodium_ctx = self._node().sodium_ctx();
+        sodium_ctx.transaction(|| {
+            let active: CellLoop<bool> = CellLoop::new(&sodium_ctx);
+            let starts = self.gate(active.to_cell().map(|active: &bool| !*active));
+            let inner = Cell::switch_s(starts.map(f).hold(Stream::new(&sodium_ctx))).gate(active.to_cell());
+            active.loop_(
+                starts.map(|_: &A| true)
+                    .merge(inner.filter(|b_op: &Option<B>| b_op.is_none()).map(|_: &Option<B>| false), |l: &bool, _r: &bool| *l)
+                    .hold(false)
+            );
+            inner.filter_option()
+        })
+    }
+
+    /// Merges the streams currently held in `csa`, combining simultaneous
+    /// events with `f` in the order of the `Vec`. A new set of streams is
+    /// used from the end of the transaction it is set in, like
+    /// `Cell::switch_s`.
+    pub fn merge_all<FN:Fn(&A,&A)->A+'static>(csa: Cell<Vec<Stream<A>>>, f: FN) -> Stream<A> {
+        let sodium_ctx = csa._node().sodium_ctx();
+        let sodium_ctx = &sodium_ctx;
+        let mut gc_ctx = sodium_ctx.gc_ctx();
+        let gc_ctx = &mut gc_ctx;
+        let streams_init = csa.sample_no_trans();
+        let value: Gc<UnsafeCell<Option<MemoLazy<A>>>> = gc_ctx.new_gc_with_desc(UnsafeCell::new(None), String::from("Stream::merge_all_value"));
+        let streams: Gc<UnsafeCell<Vec<Stream<A>>>> = gc_ctx.new_gc_with_desc(UnsafeCell::new(streams_init.clone()), String::from("Stream::merge_all_streams"));
+        let node2;
+        {
+            let sodium_ctx2 = sodium_ctx.clone();
+            let value = value.clone();
+            let streams = streams.clone();
+            let f = Rc::new(f);
+            let node2_update_deps = vec![Dep { gc_dep: value.to_dep() }, Dep { gc_dep: streams.to_dep() }];
+            node2 = Node::new(
+                sodium_ctx,
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    let streams = unsafe { &*(*streams).get() };
+                    let mut result_op: Option<MemoLazy<A>> = None;
+                    for sa in streams {
+                        if let Some(sa_value) = sa.peek_value() {
+                            result_op = match result_op {
+                                Some(lhs) => {
+                                    let f = f.clone();
+                                    Some(sodium_ctx.new_lazy(move || f(lhs.get(), sa_value.get())))
+                                },
+                                None => Some(sa_value)
+                            };
+                        }
+                    }
+                    if result_op.is_none() {
+                        return false;
+                    }
+                    {
+                        let value = unsafe { &mut *(*value).get() };
+                        *value = result_op;
+                    }
+                    let value = value.clone();
+                    sodium_ctx.post(move || {
+                        let value = unsafe { &mut *(*value).get() };
+                        *value = None;
+                    });
+                    true
+                },
+                node2_update_deps,
+                streams_init.iter().map(|sa| sa._node().clone()).collect(),
+                || {},
+                String::from("Stream::merge_all_node2")
+            );
+        }
+        let result = Stream {
+            data: gc_ctx.new_gc_with_desc(UnsafeCell::new(StreamData {
+                value: value.clone(),
+                node: node2.clone()
+            }), String::from("Stream::merge_all"))
+        };
+        let node1_deps = vec![csa._node().clone()];
+        let node1;
+        let node1_self: Gc<UnsafeCell<Option<Node>>> = gc_ctx.new_gc_with_desc(UnsafeCell::new(None), String::from("Stream::merge_all_node1_self"));
+        {
+            let sodium_ctx2 = sodium_ctx.clone();
+            let node2 = node2.clone();
+            let node1_update_deps = vec![csa.to_dep(), node2.to_dep(), Dep { gc_dep: streams.to_dep() }, Dep { gc_dep: node1_self.to_dep() }];
+            let node1_self = node1_self.clone();
+            node1 = Node::new(
+                sodium_ctx,
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    let node2 = node2.clone();
+                    let csa = csa.clone();
+                    let streams = streams.clone();
+                    let node1_self = node1_self.clone();
+                    sodium_ctx.post(move || {
+                        let new_streams = csa.sample_no_trans();
+                        let node1 = unsafe { &*(*node1_self).get() }.clone().unwrap();
+                        node2.remove_all_dependencies();
+                        node2.ensure_bigger_than(node1.rank());
+                        for sa in &new_streams {
+                            node2.ensure_bigger_than(sa._node().rank());
+                        }
+                        node2.add_dependencies(vec![node1]);
+                        node2.add_dependencies(new_streams.iter().map(|sa| sa._node().clone()).collect());
+                        let streams = unsafe { &mut *(*streams).get() };
+                        *streams = new_streams;
+                    });
+                    false
+                },
+                node1_update_deps,
+                node1_deps,
+                || {},
+                String::from("Stream::merge_all_node1")
+            );
+        }
+        {
+            let node1_self = unsafe { &mut *(*node1_self).get() };
+            *node1_self = Some(node1.clone());
+        }
+        node2.ensure_bigger_than(node1.rank());
+        node2.add_dependencies(vec![node1]);
+        result
+    }
+
+    /// Merges a set of keyed streams into events listing the keys that fired
+    /// in the transaction, in key order. Each event of `changes` replaces
+    /// (`Some`) or removes (`None`) the stream for a key, from the end of the
+    /// transaction. Only the keys named are subscribed or unsubscribed, the
+    /// other streams are left as they are.
+    pub fn merge_keyed<K: Ord + Clone + Trace + Finalize + 'static>(
+        sodium_ctx: &SodiumCtx,
+        initial: Vec<(K,Stream<A>)>,
+        changes: Stream<Vec<(K,Option<Stream<A>>)>>
+    ) -> Stream<Vec<(K,A)>> {
+        let mut gc_ctx = sodium_ctx.gc_ctx();
+        let gc_ctx = &mut gc_ctx;
+        let value = gc_ctx.new_gc_with_desc(UnsafeCell::new(None), String::from("Stream::merge_keyed_value"));
+        let fired: MergeKeyedFired<K,A> = gc_ctx.new_gc_with_desc(UnsafeCell::new(Vec::new()), String::from("Stream::merge_keyed_fired"));
+        let entries: Gc<UnsafeCell<BTreeMap<K,Node>>> = gc_ctx.new_gc_with_desc(UnsafeCell::new(BTreeMap::new()), String::from("Stream::merge_keyed_entries"));
+        let node2;
+        {
+            let sodium_ctx2 = sodium_ctx.clone();
+            let value = value.clone();
+            let fired = fired.clone();
+            let node2_update_deps = vec![Dep { gc_dep: value.to_dep() }, Dep { gc_dep: fired.to_dep() }];
+            node2 = Node::new(
+                sodium_ctx,
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    let mut fired = {
+                        let fired = unsafe { &mut *(*fired).get() };
+                        ::std::mem::take(fired)
+                    };
+                    if fired.is_empty() {
+                        return false;
+                    }
+                    fired.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+                    {
+                        let value = unsafe { &mut *(*value).get() };
+                        *value = Some(sodium_ctx.new_lazy(move || {
+                            fired.iter().map(|(k, a)| (k.clone(), a.get().clone())).collect()
+                        }));
+                    }
+                    let value = value.clone();
+                    sodium_ctx.post(move || {
+                        let value = unsafe { &mut *(*value).get() };
+                        *value = None;
+                    });
+                    true
+                },
+                node2_update_deps,
+                Vec::new(),
+                || {},
+                String::from("Stream::merge_keyed_node2")
+            );
+        }
+        for (k, sa) in initial {
+            let entry = merge_keyed_entry(sodium_ctx, k.clone(), sa, &fired);
+            node2.ensure_bigger_than(entry.rank());
+            node2.add_dependencies(vec![entry.clone()]);
+            let entries = unsafe { &mut *(*entries).get() };
+            entries.insert(k, entry);
+        }
+        let result = Stream {
+            data: gc_ctx.new_gc_with_desc(UnsafeCell::new(StreamData {
+                value: value.clone(),
+                node: node2.clone()
+            }), String::from("Stream::merge_keyed"))
+        };
+        let node1;
+        {
+            let sodium_ctx2 = sodium_ctx.clone();
+            let node2 = node2.clone();
+            let node1_update_deps = vec![changes.to_dep(), node2.to_dep(), Dep { gc_dep: entries.to_dep() }, Dep { gc_dep: fired.to_dep() }];
+            let changes2 = changes.clone();
+            node1 = Node::new(
+                sodium_ctx,
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    let changes = match changes2.peek_value() {
+                        Some(changes) => changes,
+                        None => return false
+                    };
+                    let sodium_ctx3 = sodium_ctx.clone();
+                    let node2 = node2.clone();
+                    let entries = entries.clone();
+                    let fired = fired.clone();
+                    sodium_ctx.post(move || {
+                        let sodium_ctx = &sodium_ctx3;
+                        let entries = unsafe { &mut *(*entries).get() };
+                        for (k, sa_op) in changes.get() {
+                            if let Some(entry) = entries.remove(k) {
+                                node2.remove_dependency(&entry);
+                            }
+                            if let Some(ref sa) = *sa_op {
+                                let entry = merge_keyed_entry(sodium_ctx, k.clone(), sa.clone(), &fired);
+                                node2.ensure_bigger_than(entry.rank());
+                                node2.add_dependencies(vec![entry.clone()]);
+                                entries.insert(k.clone(), entry);
+                            }
+                        }
+                    });
+                    false
+                },
+                node1_update_deps,
+                vec![changes._node().clone()],
+                || {},
+                String::from("Stream::merge_keyed_node1")
+            );
+        }
+        node2.ensure_bigger_than(node1.rank());
+        node2.add_dependencies(vec![node1]);
+        result
     }
 
     pub fn add_cleanup<CLEANUP:IsLambdaMut0<()>+'static>(&self, cleanup: CLEANUP) {
@@ -524,4 +877,33 @@ impl<A:Finalize> Finalize for Stream<A> {
     fn finalize(&mut self) {
         self.data.finalize();
     }
-}
\ No newline at end of file
+}
+
+/// The node standing for one key of `Stream::merge_keyed`, it records the
+/// key when its stream fires.
+fn merge_keyed_entry<K,A>(sodium_ctx: &SodiumCtx, k: K, sa: Stream<A>, fired: &MergeKeyedFired<K,A>) -> Node
+    where K: Clone + Trace + Finalize + 'static,
+          A: Clone + Trace + Finalize + 'static
+{
+    let k = sodium_ctx.gc_ctx().new_gc_with_desc(k, String::from("Stream::merge_keyed_key"));
+    let update_deps = vec![sa.to_dep(), Dep { gc_dep: k.to_dep() }, Dep { gc_dep: fired.to_dep() }];
+    let deps = vec![sa._node().clone()];
+    let fired = fired.clone();
+    Node::new(
+        sodium_ctx,
+        move || {
+            match sa.peek_value() {
+                Some(a) => {
+                    let fired = unsafe { &mut *(*fired).get() };
+                    fired.push(((*k).clone(), a));
+                    true
+                },
+                None => false
+            }
+        },
+        update_deps,
+        deps,
+        || {},
+        String::from("Stream::merge_keyed_entry")
+    )
+}
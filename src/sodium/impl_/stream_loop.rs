@@ -3,6 +3,7 @@ use sodium::impl_::Latch;
 use sodium::impl_::MemoLazy;
 use sodium::impl_::Node;
 use sodium::impl_::SodiumCtx;
+use sodium::impl_::SodiumError;
 use sodium::impl_::Stream;
 use sodium::impl_::gc::Finalize;
 use sodium::impl_::gc::Gc;
@@ -17,16 +18,30 @@ pub struct StreamLoop<A> {
 
 impl<A: Trace + Finalize + Clone + 'static> StreamLoop<A> {
     pub fn new(sodium_ctx: &SodiumCtx) -> StreamLoop<A> {
+        let stream = Stream::new(sodium_ctx);
+        let looped = Rc::new(UnsafeCell::new(false));
+        {
+            let looped = Rc::downgrade(&looped);
+            sodium_ctx.register_loop(stream._node().id(), move || {
+                looped.upgrade().map(|looped| unsafe { *(*looped).get() }).unwrap_or(true)
+            });
+        }
         StreamLoop {
-            stream: Stream::new(sodium_ctx),
-            looped: Rc::new(UnsafeCell::new(false))
+            stream,
+            looped
         }
     }
 
     pub fn loop_(&self, sa: Stream<A>) {
+        if self.try_loop(sa).is_err() {
+            panic!("StreamLoop looped more than once.");
+        }
+    }
+
+    pub fn try_loop(&self, sa: Stream<A>) -> Result<(),SodiumError> {
         let looped = unsafe { &mut *(*self.looped).get() };
         if *looped {
-            panic!("StreamLoop looped more than once.");
+            return Err(SodiumError::AlreadyLooped);
         }
         let value = self.stream._value().clone();
         let update_deps = vec![sa.to_dep(), Dep { gc_dep: value.to_dep() }];
@@ -52,6 +67,7 @@ impl<A: Trace + Finalize + Clone + 'static> StreamLoop<A> {
         node.ensure_bigger_than(sa_node.rank());
         node.add_dependencies(vec![sa_node]);
         *looped = true;
+        Ok(())
     }
 
     pub fn to_stream(&self) -> Stream<A> {
@@ -1,8 +1,10 @@
+use sodium::impl_::CallbackSendMode;
 use sodium::impl_::Dep;
 use sodium::impl_::Stream;
 use sodium::impl_::StreamData;
 use sodium::impl_::MemoLazy;
 use sodium::impl_::Node;
+use sodium::impl_::SinkSender;
 use sodium::impl_::SodiumCtx;
 use sodium::gc::Finalize;
 use sodium::gc::Gc;
@@ -12,12 +14,16 @@ use std::cell::UnsafeCell;
 use std::mem::swap;
 use std::rc::Rc;
 
+pub type SendHook<A> = Box<dyn Fn(&A,u64)>;
+
 pub struct StreamSink<A> {
     value: Gc<UnsafeCell<Option<MemoLazy<A>>>>,
     next_value: Gc<UnsafeCell<Option<MemoLazy<A>>>>,
     node: Node,
     will_clear: Rc<UnsafeCell<bool>>,
-    coalescer_op: Option<Rc<Fn(&A,&A)->A>>
+    coalescer_op: Option<Rc<Fn(&A,&A)->A>>,
+    callback_send_mode_op: Rc<UnsafeCell<Option<CallbackSendMode>>>,
+    send_hooks: Rc<UnsafeCell<Vec<SendHook<A>>>>
 }
 
 impl<A: Trace + Finalize + Clone + 'static> StreamSink<A> {
@@ -53,16 +59,32 @@ impl<A: Trace + Finalize + Clone + 'static> StreamSink<A> {
                 String::from("StreamSink::new_node")
             ),
             will_clear: Rc::new(UnsafeCell::new(false)),
-            coalescer_op: coalescer_op
+            coalescer_op: coalescer_op,
+            callback_send_mode_op: Rc::new(UnsafeCell::new(None)),
+            send_hooks: Rc::new(UnsafeCell::new(Vec::new()))
         }
     }
 
     pub fn send(&self, value: A) {
         let sodium_ctx = self.node.sodium_ctx();
         if sodium_ctx.callback_depth() > 0 {
-            panic!("StreamSink::send / CellSink::send can not be called from a sodium callback, consider using SodiumCtx::post to send after the end of transaction.")
+            match self.callback_send_mode() {
+                CallbackSendMode::Panic =>
+                    panic!("StreamSink::send / CellSink::send can not be called from a sodium callback, consider using SodiumCtx::post to send after the end of transaction, or CallbackSendMode::Defer."),
+                CallbackSendMode::Defer => {
+                    let self_ = self.clone();
+                    sodium_ctx.defer_send(move || self_.send(value));
+                    return;
+                }
+            }
         }
         sodium_ctx.transaction(|| {
+            if !sodium_ctx.is_propergating() {
+                let send_hooks = unsafe { &*(*self.send_hooks).get() };
+                for send_hook in send_hooks {
+                    send_hook(&value, sodium_ctx.transaction_seq());
+                }
+            }
             let will_clear = unsafe { &mut *(*self.will_clear).get() };
             if !*will_clear {
                 *will_clear = true;
@@ -95,6 +117,27 @@ impl<A: Trace + Finalize + Clone + 'static> StreamSink<A> {
         });
     }
 
+    /// Calls hook with each value sent from outside of a transaction's
+    /// propagation, along with the transaction's `transaction_seq`.
+    pub fn add_send_hook<HOOK: Fn(&A,u64) + 'static>(&self, hook: HOOK) {
+        let send_hooks = unsafe { &mut *(*self.send_hooks).get() };
+        send_hooks.push(Box::new(hook));
+    }
+
+    pub fn callback_send_mode(&self) -> CallbackSendMode {
+        let callback_send_mode_op = unsafe { &*(*self.callback_send_mode_op).get() };
+        callback_send_mode_op.unwrap_or_else(|| self.node.sodium_ctx().callback_send_mode())
+    }
+
+    pub fn set_callback_send_mode(&self, mode: CallbackSendMode) {
+        unsafe { *(*self.callback_send_mode_op).get() = Some(mode); }
+    }
+
+    pub fn sender(&self) -> SinkSender<A> where A: Send {
+        let self_ = self.clone();
+        self.node.sodium_ctx().new_sink_sender(move |a: A| self_.send(a))
+    }
+
     pub fn to_stream(&self) -> Stream<A> {
         let mut gc_ctx = self.node.sodium_ctx().gc_ctx();
         Stream {
@@ -113,7 +156,9 @@ impl<A: Clone + Trace + Finalize + 'static> Clone for StreamSink<A> {
             next_value: self.next_value.clone(),
             node: self.node.clone(),
             will_clear: self.will_clear.clone(),
-            coalescer_op: self.coalescer_op.clone()
+            coalescer_op: self.coalescer_op.clone(),
+            callback_send_mode_op: self.callback_send_mode_op.clone(),
+            send_hooks: self.send_hooks.clone()
         }
     }
 }
@@ -0,0 +1,393 @@
+use sodium::impl_::Cell;
+use sodium::impl_::CellSink;
+use sodium::impl_::Clock;
+use sodium::impl_::Dep;
+use sodium::impl_::Lambda;
+use sodium::impl_::SodiumCtx;
+use sodium::impl_::Stream;
+use sodium::impl_::WeakNode;
+use sodium::gc::Finalize;
+use sodium::gc::Gc;
+use sodium::gc::Trace;
+use std::cell::RefCell;
+use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+pub struct TimerSystem {
+    data: Rc<UnsafeCell<TimerSystemData>>
+}
+
+struct TimerSystemData {
+    sodium_ctx: SodiumCtx,
+    clock: Box<dyn Clock>,
+    time: CellSink<Duration>,
+    alarms: BTreeMap<AlarmId,Box<dyn FnMut()>>,
+    next_seq: u64
+}
+
+struct AtState {
+    requested: Option<Duration>,
+    alarm_op: Option<AlarmId>
+}
+
+struct AlarmSlot {
+    requested: Option<Duration>,
+    alarm_op: Option<AlarmId>,
+    fired: Option<Duration>
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Debug)]
+pub struct AlarmId {
+    time: Duration,
+    seq: u64
+}
+
+impl AlarmId {
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+}
+
+impl TimerSystem {
+    pub fn new<CLOCK: Clock + 'static>(sodium_ctx: &SodiumCtx, clock: CLOCK) -> TimerSystem {
+        let now = clock.now();
+        TimerSystem {
+            data: Rc::new(UnsafeCell::new(TimerSystemData {
+                sodium_ctx: sodium_ctx.clone(),
+                clock: Box::new(clock),
+                time: CellSink::new(sodium_ctx, now),
+                alarms: BTreeMap::new(),
+                next_seq: 0
+            }))
+        }
+    }
+
+    pub fn sodium_ctx(&self) -> SodiumCtx {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.sodium_ctx.clone()
+    }
+
+    pub fn now(&self) -> Duration {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.clock.now()
+    }
+
+    pub fn time(&self) -> Cell<Duration> {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.time.to_cell()
+    }
+
+    pub fn set_alarm<F: FnMut() + 'static>(&self, time: Duration, f: F) -> AlarmId {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        let id = AlarmId {
+            time,
+            seq: self_.next_seq
+        };
+        self_.next_seq += 1;
+        self_.alarms.insert(id, Box::new(f));
+        id
+    }
+
+    pub fn cancel_alarm(&self, id: AlarmId) {
+        let self_ = unsafe { &mut *(*self.data).get() };
+        self_.alarms.remove(&id);
+    }
+
+    pub fn next_alarm_time(&self) -> Option<Duration> {
+        let self_ = unsafe { &*(*self.data).get() };
+        self_.alarms.keys().next().map(|id| id.time)
+    }
+
+    pub fn run_timers(&self) {
+        let now = self.now();
+        let sodium_ctx = self.sodium_ctx();
+        loop {
+            let alarm_op;
+            {
+                let self_ = unsafe { &mut *(*self.data).get() };
+                let id_op = self_.alarms.keys().next().cloned();
+                alarm_op = match id_op {
+                    Some(id) if id.time <= now => self_.alarms.remove(&id).map(|f| (id, f)),
+                    _ => None
+                };
+            }
+            match alarm_op {
+                Some((id, mut f)) => {
+                    sodium_ctx.transaction(|| {
+                        self.set_time(id.time);
+                        f();
+                    });
+                },
+                None => break
+            }
+        }
+        self.set_time(now);
+    }
+
+    fn set_time(&self, time: Duration) {
+        let self_ = unsafe { &*(*self.data).get() };
+        if self_.time.to_cell().sample_no_trans() != time {
+            self_.time.send(time);
+        }
+    }
+
+    pub fn at(&self, c_time: Cell<Option<Duration>>) -> Stream<Duration> {
+        let sodium_ctx = self.sodium_ctx();
+        let sodium_ctx = &sodium_ctx;
+        let node_self: Rc<UnsafeCell<Option<WeakNode>>> = Rc::new(UnsafeCell::new(None));
+        let fired: Rc<UnsafeCell<Option<Duration>>> = Rc::new(UnsafeCell::new(None));
+        let state: Rc<UnsafeCell<AtState>> = Rc::new(UnsafeCell::new(AtState { requested: None, alarm_op: None }));
+        let update_deps = vec![c_time.to_dep()];
+        let deps = vec![c_time._node().clone()];
+        let sodium_ctx2 = sodium_ctx.clone();
+        let update;
+        {
+            let timer_system = self.clone();
+            let node_self = node_self.clone();
+            let fired = fired.clone();
+            let state = state.clone();
+            update = Lambda::new(
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    let time_op = *c_time._next_value_thunk().get();
+                    let state2 = unsafe { &mut *(*state).get() };
+                    if state2.requested != time_op {
+                        state2.requested = time_op;
+                        if let Some(alarm) = state2.alarm_op.take() {
+                            timer_system.cancel_alarm(alarm);
+                        }
+                        if let Some(time) = time_op {
+                            let node_self = node_self.clone();
+                            let fired = fired.clone();
+                            let state = state.clone();
+                            state2.alarm_op = Some(timer_system.set_alarm(time, move || {
+                                unsafe { (*(*state).get()).alarm_op = None; }
+                                unsafe { *(*fired).get() = Some(time); }
+                                let node_self = unsafe { &*(*node_self).get() };
+                                if let Some(node) = node_self.as_ref().and_then(|node| node.upgrade()) {
+                                    node.mark_dirty();
+                                }
+                            }));
+                        }
+                    }
+                    let fired = unsafe { &mut *(*fired).get() };
+                    fired.take().map(|time| sodium_ctx.new_lazy(move || time))
+                },
+                update_deps
+            );
+        }
+        let timer_system = self.clone();
+        let result = Stream::_new(
+            sodium_ctx,
+            update,
+            deps,
+            move || {
+                let state = unsafe { &mut *(*state).get() };
+                if let Some(alarm) = state.alarm_op.take() {
+                    timer_system.cancel_alarm(alarm);
+                }
+            },
+            "TimerSystem::at"
+        );
+        unsafe {
+            *(*node_self).get() = Some(result._node().downgrade());
+        }
+        result
+    }
+
+    /// Emits the input after it has been quiet for `duration`.
+    pub fn debounce<A: Clone + Trace + Finalize + 'static>(&self, sa: Stream<A>, duration: Duration) -> Stream<A> {
+        self.timed(
+            Some(sa),
+            None,
+            move |pending: &mut Option<A>, input_op, fired_op, now, alarm| {
+                if let Some(a) = input_op {
+                    *pending = Some(a);
+                    *alarm = Some(now + duration);
+                }
+                if fired_op.is_some() {
+                    return pending.take();
+                }
+                None
+            },
+            "TimerSystem::debounce"
+        )
+    }
+
+    /// Passes an event through, then drops further events until `duration`
+    /// has passed.
+    pub fn throttle<A: Clone + Trace + Finalize + 'static>(&self, sa: Stream<A>, duration: Duration) -> Stream<A> {
+        self.timed(
+            Some(sa),
+            (),
+            move |_: &mut (), input_op, _fired_op, now, alarm| {
+                match input_op {
+                    Some(a) if alarm.is_none() => {
+                        *alarm = Some(now + duration);
+                        Some(a)
+                    },
+                    _ => None
+                }
+            },
+            "TimerSystem::throttle"
+        )
+    }
+
+    /// Re-emits every event `duration` after it arrived.
+    pub fn delay<A: Clone + Trace + Finalize + 'static>(&self, sa: Stream<A>, duration: Duration) -> Stream<A> {
+        self.timed(
+            Some(sa),
+            VecDeque::new(),
+            move |queue: &mut VecDeque<(Duration,A)>, input_op, fired_op, now, alarm| {
+                if let Some(a) = input_op {
+                    queue.push_back((now + duration, a));
+                }
+                let result = match fired_op {
+                    Some(_) => queue.pop_front().map(|(_, a)| a),
+                    None => None
+                };
+                *alarm = queue.front().map(|&(time, _)| time);
+                result
+            },
+            "TimerSystem::delay"
+        )
+    }
+
+    /// Fires with the current time once `duration` passes without an event,
+    /// counting from creation or from the last event.
+    pub fn timeout<A: Clone + Trace + Finalize + 'static>(&self, sa: Stream<A>, duration: Duration) -> Stream<Duration> {
+        let mut started = false;
+        self.timed(
+            Some(sa),
+            (),
+            move |_: &mut (), input_op, fired_op, now, alarm| {
+                if input_op.is_some() || !started {
+                    started = true;
+                    *alarm = Some(now + duration);
+                }
+                fired_op
+            },
+            "TimerSystem::timeout"
+        )
+    }
+
+    /// Fires with the current time every `duration`, starting one
+    /// `duration` from now.
+    pub fn periodic(&self, duration: Duration) -> Stream<Duration> {
+        self.timed(
+            None as Option<Stream<()>>,
+            (),
+            move |_: &mut (), _input_op: Option<()>, fired_op, now, alarm| {
+                match fired_op {
+                    Some(time) => *alarm = Some(time + duration),
+                    None if alarm.is_none() => *alarm = Some(now + duration),
+                    None => ()
+                }
+                fired_op
+            },
+            "TimerSystem::periodic"
+        )
+    }
+
+    /// Builds a stream from a step function that sees the input event (if
+    /// any), the time of a fired alarm (if any) and the current time. It keeps
+    /// at most one alarm scheduled, whose time the step function reads and
+    /// writes through its last argument; a fired alarm reads as None. The
+    /// pending alarm is cancelled when the node is cleaned up.
+    fn timed<A,B,S,STEP>(&self, sa_op: Option<Stream<A>>, init_state: S, step: STEP, desc: &'static str) -> Stream<B>
+        where A: Clone + Trace + Finalize + 'static,
+              B: Clone + Trace + Finalize + 'static,
+              S: Trace + Finalize + 'static,
+              STEP: FnMut(&mut S, Option<A>, Option<Duration>, Duration, &mut Option<Duration>) -> Option<B> + 'static
+    {
+        let sodium_ctx = self.sodium_ctx();
+        let sodium_ctx = &sodium_ctx;
+        let node_self: Rc<UnsafeCell<Option<WeakNode>>> = Rc::new(UnsafeCell::new(None));
+        let slot: Rc<UnsafeCell<AlarmSlot>> = Rc::new(UnsafeCell::new(AlarmSlot { requested: None, alarm_op: None, fired: None }));
+        let state: Gc<UnsafeCell<S>> = sodium_ctx.gc_ctx().new_gc_with_desc(UnsafeCell::new(init_state), String::from(desc) + "_state");
+        let mut update_deps = vec![Dep { gc_dep: state.to_dep() }];
+        let mut deps = Vec::new();
+        if let Some(ref sa) = sa_op {
+            update_deps.push(sa.to_dep());
+            deps.push(sa._node().clone());
+        }
+        let step = RefCell::new(step);
+        let sodium_ctx2 = sodium_ctx.clone();
+        let update;
+        {
+            let timer_system = self.clone();
+            let node_self = node_self.clone();
+            let slot = slot.clone();
+            update = Lambda::new(
+                move || {
+                    let sodium_ctx = &sodium_ctx2;
+                    let input_op = sa_op.as_ref().and_then(|sa| sa.peek_value()).map(|thunk| thunk.get().clone());
+                    let slot2 = unsafe { &mut *(*slot).get() };
+                    let fired_op = slot2.fired.take();
+                    if fired_op.is_some() {
+                        slot2.requested = None;
+                    }
+                    let mut requested = slot2.requested;
+                    let state = unsafe { &mut *(*state).get() };
+                    let result = (*step.borrow_mut())(state, input_op, fired_op, timer_system.now(), &mut requested);
+                    if requested != slot2.requested {
+                        timer_system.set_slot_alarm(&slot, &node_self, requested);
+                    }
+                    result.map(|b| sodium_ctx.new_lazy(move || b.clone()))
+                },
+                update_deps
+            );
+        }
+        let timer_system = self.clone();
+        let result = Stream::_new(
+            sodium_ctx,
+            update,
+            deps,
+            move || {
+                let slot = unsafe { &mut *(*slot).get() };
+                if let Some(alarm) = slot.alarm_op.take() {
+                    timer_system.cancel_alarm(alarm);
+                }
+            },
+            desc
+        );
+        unsafe {
+            *(*node_self).get() = Some(result._node().downgrade());
+        }
+        result
+    }
+
+    fn set_slot_alarm(&self, slot: &Rc<UnsafeCell<AlarmSlot>>, node_self: &Rc<UnsafeCell<Option<WeakNode>>>, time_op: Option<Duration>) {
+        let slot2 = unsafe { &mut *(**slot).get() };
+        slot2.requested = time_op;
+        if let Some(alarm) = slot2.alarm_op.take() {
+            self.cancel_alarm(alarm);
+        }
+        if let Some(time) = time_op {
+            let slot = slot.clone();
+            let node_self = node_self.clone();
+            slot2.alarm_op = Some(self.set_alarm(time, move || {
+                {
+                    let slot = unsafe { &mut *(*slot).get() };
+                    slot.alarm_op = None;
+                    slot.fired = Some(time);
+                }
+                let node_self = unsafe { &*(*node_self).get() };
+                if let Some(node) = node_self.as_ref().and_then(|node| node.upgrade()) {
+                    node.mark_dirty();
+                }
+            }));
+        }
+    }
+}
+
+impl Clone for TimerSystem {
+    fn clone(&self) -> Self {
+        TimerSystem {
+            data: self.data.clone()
+        }
+    }
+}
@@ -0,0 +1,30 @@
+/// Receives events from inside `SodiumCtx::propergate`. Install one with
+/// `SodiumCtx::add_transaction_observer`. Observers must not call back into
+/// the `SodiumCtx` they are observing.
+///
+/// A `post` closure that starts a transaction of its own produces a nested
+/// `transaction_start` / `transaction_end` pair.
+pub trait TransactionObserver {
+    fn transaction_start(&mut self) {}
+
+    fn transaction_end(&mut self) {}
+
+    fn node_marked_dirty(&mut self, _id: u32, _desc: &str, _rank: u32) {}
+
+    fn node_update_start(&mut self, _id: u32, _desc: &str, _rank: u32) {}
+
+    /// `changed` is true when the update marked the node's dependents dirty.
+    fn node_update_end(&mut self, _id: u32, _desc: &str, _rank: u32, _changed: bool) {}
+
+    fn hook_start(&mut self, _kind: HookKind) {}
+
+    fn hook_end(&mut self, _kind: HookKind) {}
+}
+
+/// The closures queued with `SodiumCtx::pre`, `post` and `after_trans`.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum HookKind {
+    Pre,
+    Post,
+    AfterTrans
+}
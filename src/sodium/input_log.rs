@@ -0,0 +1,180 @@
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use sodium::CellSink;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use sodium::gc::Finalize;
+use sodium::gc::Trace;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+use std::rc::Weak;
+
+type Decoder = Box<dyn Fn(&serde_json::Value)->serde_json::Result<Box<dyn FnOnce()>>>;
+
+/// A value sent into the sink registered under `sink`.
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct InputSend {
+    pub sink: String,
+    pub value: serde_json::Value
+}
+
+/// Sink sends grouped by the transaction they were made in, oldest first.
+#[derive(Clone,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct InputLog {
+    pub transactions: Vec<Vec<InputSend>>
+}
+
+/// Records every send made into the registered sinks from outside of
+/// propagation. Sends made from `post`, `after_trans` or deferred callbacks
+/// are left out, as replaying the inputs produces them again.
+pub struct InputRecorder {
+    data: Rc<RefCell<InputRecorderData>>
+}
+
+struct InputRecorderData {
+    log: InputLog,
+    last_seq_op: Option<u64>
+}
+
+impl InputRecorder {
+    pub fn new() -> InputRecorder {
+        InputRecorder {
+            data: Rc::new(RefCell::new(InputRecorderData {
+                log: InputLog::default(),
+                last_seq_op: None
+            }))
+        }
+    }
+
+    pub fn stream_sink<A: Clone + Trace + Finalize + Serialize + 'static>(&self, id: &str, sink: &StreamSink<A>) {
+        let hook = self.hook(id);
+        sink.impl_.add_send_hook(move |a: &A, seq: u64| hook(serde_json::to_value(a), seq));
+    }
+
+    pub fn cell_sink<A: Clone + Trace + Finalize + Serialize + 'static>(&self, id: &str, sink: &CellSink<A>) {
+        let hook = self.hook(id);
+        sink.impl_.add_send_hook(move |a: &A, seq: u64| hook(serde_json::to_value(a), seq));
+    }
+
+    pub fn log(&self) -> InputLog {
+        self.data.borrow().log.clone()
+    }
+
+    pub fn clear(&self) {
+        let mut data = self.data.borrow_mut();
+        data.log.transactions.clear();
+        data.last_seq_op = None;
+    }
+
+    fn hook(&self, id: &str) -> impl Fn(serde_json::Result<serde_json::Value>, u64) {
+        let data: Weak<RefCell<InputRecorderData>> = Rc::downgrade(&self.data);
+        let id = id.to_string();
+        move |value, seq| {
+            let data = match data.upgrade() {
+                Some(data) => data,
+                None => return
+            };
+            let value = value.unwrap_or_else(|err| panic!("InputRecorder: can not serialize value sent to \"{}\": {}", id, err));
+            let mut data = data.borrow_mut();
+            if data.last_seq_op != Some(seq) {
+                data.last_seq_op = Some(seq);
+                data.log.transactions.push(Vec::new());
+            }
+            if let Some(transaction) = data.log.transactions.last_mut() {
+                transaction.push(InputSend { sink: id.clone(), value });
+            }
+        }
+    }
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        InputRecorder::new()
+    }
+}
+
+impl Clone for InputRecorder {
+    fn clone(&self) -> Self {
+        InputRecorder {
+            data: self.data.clone()
+        }
+    }
+}
+
+/// Drives a freshly built graph with an `InputLog`. Register the rebuilt
+/// sinks under the ids they were recorded with, then call `replay`.
+pub struct InputReplayer {
+    sodium_ctx: SodiumCtx,
+    sinks: HashMap<String,Decoder>
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    UnknownSink(String),
+    Decode { sink: String, error: serde_json::Error }
+}
+
+impl InputReplayer {
+    pub fn new(sodium_ctx: &SodiumCtx) -> InputReplayer {
+        InputReplayer {
+            sodium_ctx: sodium_ctx.clone(),
+            sinks: HashMap::new()
+        }
+    }
+
+    pub fn stream_sink<A: Clone + Trace + Finalize + DeserializeOwned + 'static>(&mut self, id: &str, sink: &StreamSink<A>) {
+        let sink = sink.clone();
+        self.sinks.insert(id.to_string(), Box::new(move |value: &serde_json::Value| {
+            let a: A = serde_json::from_value(value.clone())?;
+            let sink = sink.clone();
+            let send: Box<dyn FnOnce()> = Box::new(move || sink.send(&a));
+            Ok(send)
+        }));
+    }
+
+    pub fn cell_sink<A: Clone + Trace + Finalize + DeserializeOwned + 'static>(&mut self, id: &str, sink: &CellSink<A>) {
+        let sink = sink.clone();
+        self.sinks.insert(id.to_string(), Box::new(move |value: &serde_json::Value| {
+            let a: A = serde_json::from_value(value.clone())?;
+            let sink = sink.clone();
+            let send: Box<dyn FnOnce()> = Box::new(move || sink.send(&a));
+            Ok(send)
+        }));
+    }
+
+    /// Sends each recorded transaction in its own transaction. A transaction
+    /// is decoded in full before any of it is sent, so an error leaves the
+    /// graph at the end of the previous one.
+    pub fn replay(&self, log: &InputLog) -> Result<(),ReplayError> {
+        for transaction in &log.transactions {
+            let mut sends = Vec::with_capacity(transaction.len());
+            for input_send in transaction {
+                let decode = self.sinks.get(&input_send.sink).ok_or_else(|| ReplayError::UnknownSink(input_send.sink.clone()))?;
+                let send = decode(&input_send.value).map_err(|error| ReplayError::Decode { sink: input_send.sink.clone(), error })?;
+                sends.push(send);
+            }
+            self.sodium_ctx.transaction(move |_| {
+                for send in sends {
+                    send();
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::UnknownSink(ref sink) => write!(f, "no sink registered for \"{}\"", sink),
+            ReplayError::Decode { ref sink, ref error } => write!(f, "can not decode value for \"{}\": {}", sink, error)
+        }
+    }
+}
+
+impl error::Error for ReplayError {}
@@ -9,6 +9,7 @@ use sodium::IsLambda4;
 use sodium::IsLambda5;
 use sodium::IsLambda6;
 use sodium::Listener;
+use sodium::SodiumError;
 use sodium::gc::Finalize;
 use sodium::gc::Trace;
 
@@ -19,6 +20,10 @@ pub trait IsCell<A: Finalize + Trace + Clone + 'static>: Sized {
         self.to_cell().sample()
     }
 
+    fn try_sample(&self) -> Result<A,SodiumError> {
+        self.to_cell().try_sample()
+    }
+
     fn map<B: Clone + Trace + Finalize + 'static,F:IsLambda1<A,B> + 'static>(
         &self,
         f: F
@@ -50,6 +55,18 @@ pub trait IsCell<A: Finalize + Trace + Clone + 'static>: Sized {
         self.to_cell().lift6(cb, cc, cd, ce, cf, f)
     }
 
+    fn calm(&self) -> Cell<A> where A: PartialEq {
+        self.to_cell().calm()
+    }
+
+    fn switch_map_c<B,CB,F>(&self, f: F) -> Cell<B>
+        where B: Trace + Finalize + Clone + 'static,
+              CB: IsCell<B> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,CB> + 'static
+    {
+        self.to_cell().switch_map_c(f)
+    }
+
     fn add_cleanup<CLEANUP:IsLambdaMut0<()>+'static>(&self, cleanup: CLEANUP) {
         self.to_cell().add_cleanup(cleanup);
     }
@@ -12,8 +12,10 @@ use sodium::MemoLazy;
 use sodium::Stream;
 use sodium::StreamLoop;
 use sodium::StreamSink;
+use sodium::TimerSystem;
 use sodium::gc::Finalize;
 use sodium::gc::Trace;
+use std::time::Duration;
 
 pub trait IsStream<A: Finalize + Trace + Clone + 'static> {
     fn to_stream(&self) -> Stream<A>;
@@ -112,6 +114,46 @@ pub trait IsStream<A: Finalize + Trace + Clone + 'static> {
         self.to_stream().snapshot6(cb, cc, cd, ce, cf, f)
     }
 
+    fn snapshot_all<B,CB:IsCell<B>>(&self, cells: Vec<CB>) -> Stream<Vec<B>> where B: Trace + Finalize + Clone + 'static {
+        self.to_stream().snapshot_all(cells)
+    }
+
+    fn calm(&self) -> Stream<A> where A: PartialEq {
+        self.to_stream().calm()
+    }
+
+    fn switch_map<B,SB,F>(&self, f: F) -> Stream<B>
+        where B: Trace + Finalize + Clone + 'static,
+              SB: IsStream<B> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,SB> + 'static
+    {
+        self.to_stream().switch_map(f)
+    }
+
+    fn exhaust_map<B,SB,F>(&self, f: F) -> Stream<B>
+        where B: Trace + Finalize + Clone + 'static,
+              SB: IsStream<Option<B>> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,SB> + 'static
+    {
+        self.to_stream().exhaust_map(f)
+    }
+
+    fn debounce(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<A> {
+        self.to_stream().debounce(timer_system, duration)
+    }
+
+    fn throttle(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<A> {
+        self.to_stream().throttle(timer_system, duration)
+    }
+
+    fn delay(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<A> {
+        self.to_stream().delay(timer_system, duration)
+    }
+
+    fn timeout(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<Duration> {
+        self.to_stream().timeout(timer_system, duration)
+    }
+
     fn add_cleanup<CLEANUP:IsLambdaMut0<()>+'static>(&self, cleanup: CLEANUP) {
         self.to_stream().add_cleanup(cleanup);
     }
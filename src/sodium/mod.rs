@@ -1,18 +1,63 @@
 pub use self::cell::Cell;
 pub use self::cell_loop::CellLoop;
+pub use self::cell_map::CellMap;
+pub use self::cell_map::MapChange;
+pub use self::cell_map::MapDiff;
 pub use self::cell_sink::CellSink;
+#[cfg(feature = "futures")]
+pub use self::futures_compat::BufferPolicy;
+#[cfg(feature = "futures")]
+pub use self::futures_compat::ListenerStream;
+#[cfg(feature = "record")]
+pub use self::input_log::InputLog;
+#[cfg(feature = "record")]
+pub use self::input_log::InputRecorder;
+#[cfg(feature = "record")]
+pub use self::input_log::InputReplayer;
+#[cfg(feature = "record")]
+pub use self::input_log::InputSend;
+#[cfg(feature = "record")]
+pub use self::input_log::ReplayError;
+pub use self::impl_::Clock;
+pub use self::impl_::SystemClock;
+pub use self::impl_::VirtualClock;
 pub use self::is_cell::IsCell;
 pub use self::is_stream::IsStream;
 pub use self::is_stream::IsStreamOption;
 pub use self::operational::Operational;
+#[cfg(feature = "persist")]
+pub use self::persist::CellState;
+#[cfg(feature = "persist")]
+pub use self::persist::PersistError;
 pub use self::sodium_ctx::SodiumCtx;
+pub use self::sodium_thread::SodiumThread;
+pub use self::sodium_thread::SodiumThreadHandle;
+pub use self::sodium_thread::ThreadCellSink;
+pub use self::sodium_thread::ThreadStreamSink;
 pub use self::stream::Stream;
 pub use self::stream_loop::StreamLoop;
 pub use self::stream_sink::StreamSink;
+pub use self::timer_system::TimerSystem;
+pub use self::transaction_recorder::HookReport;
+pub use self::transaction_recorder::NodeUpdateReport;
+pub use self::transaction_recorder::TransactionRecorder;
+pub use self::transaction_recorder::TransactionReport;
+pub use self::impl_::CallbackSendMode;
 pub use self::impl_::Dep;
+pub use self::impl_::GraphDump;
+pub use self::impl_::HookKind;
 pub use self::impl_::Lambda;
+pub use self::impl_::LeakReport;
+pub use self::impl_::LeakedNode;
 pub use self::impl_::Listener;
+pub use self::impl_::ListenerGuard;
+pub use self::impl_::ListenerSet;
 pub use self::impl_::MemoLazy;
+pub use self::impl_::NodeDump;
+pub use self::impl_::Retention;
+pub use self::impl_::SinkSender;
+pub use self::impl_::SodiumError;
+pub use self::impl_::TransactionObserver;
 pub use self::impl_::IsLambda0;
 pub use self::impl_::IsLambdaMut0;
 pub use self::impl_::IsLambda1;
@@ -25,8 +70,13 @@ pub use self::impl_::gc;
 
 mod cell;
 mod cell_loop;
+mod cell_map;
 mod cell_sink;
+#[cfg(feature = "futures")]
+mod futures_compat;
 
+#[cfg(feature = "record")]
+mod input_log;
 mod is_cell;
 mod is_stream;
 
@@ -34,7 +84,13 @@ mod is_stream;
 mod impl_;
 
 mod operational;
+#[cfg(feature = "persist")]
+mod persist;
 mod sodium_ctx;
+mod sodium_thread;
 mod stream;
 mod stream_loop;
 mod stream_sink;
+pub mod testing;
+mod timer_system;
+mod transaction_recorder;
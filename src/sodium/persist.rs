@@ -0,0 +1,105 @@
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use sodium::Cell;
+use sodium::MemoLazy;
+use sodium::gc::Finalize;
+use sodium::gc::GcCtx;
+use sodium::gc::Trace;
+use sodium::impl_;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+type Encoder = Box<dyn Fn()->Option<serde_json::Result<serde_json::Value>>>;
+
+/// The values of the cells registered with `SodiumCtx::persist_cell`, keyed
+/// by the keys they were registered under.
+#[derive(Clone,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct CellState {
+    pub cells: BTreeMap<String,serde_json::Value>
+}
+
+#[derive(Debug)]
+pub enum PersistError {
+    Encode { key: String, error: serde_json::Error },
+    Decode { key: String, error: serde_json::Error }
+}
+
+/// The registry behind `SodiumCtx::save_state` and `SodiumCtx::load_state`.
+/// Cells are held weakly, so registering one does not keep its part of the
+/// graph alive.
+pub struct Persistence {
+    loaded: HashMap<String,serde_json::Value>,
+    cells: BTreeMap<String,Encoder>
+}
+
+impl Persistence {
+    pub fn new() -> Persistence {
+        Persistence {
+            loaded: HashMap::new(),
+            cells: BTreeMap::new()
+        }
+    }
+
+    pub fn load(&mut self, state: CellState) {
+        self.loaded = state.cells.into_iter().collect();
+    }
+
+    /// The loaded value for key, if there is one, otherwise `default`.
+    pub fn restore<A: Clone + Trace + Finalize + DeserializeOwned + 'static>(&self, gc_ctx: &mut GcCtx, key: &str, default: MemoLazy<A>) -> Result<MemoLazy<A>,PersistError> {
+        match self.loaded.get(key) {
+            Some(value) => {
+                let a: A = serde_json::from_value(value.clone()).map_err(|error| PersistError::Decode { key: key.to_string(), error })?;
+                Ok(MemoLazy::new(gc_ctx, move || a.clone()))
+            },
+            None => Ok(default)
+        }
+    }
+
+    pub fn register<A: Clone + Trace + Finalize + Serialize + 'static>(&mut self, key: &str, ca: &Cell<A>) {
+        let data = ca.impl_.data.downgrade();
+        self.cells.insert(key.to_string(), Box::new(move || {
+            data.upgrade().map(|data| serde_json::to_value(impl_::Cell { data }.sample_no_trans()))
+        }));
+    }
+
+    /// Must be called inside a transaction. Cells that have been dropped are
+    /// forgotten.
+    pub fn save(&mut self) -> Result<CellState,PersistError> {
+        let mut state = CellState::default();
+        let mut dropped = Vec::new();
+        for (key, encode) in &self.cells {
+            match encode() {
+                Some(value) => {
+                    let value = value.map_err(|error| PersistError::Encode { key: key.clone(), error })?;
+                    state.cells.insert(key.clone(), value);
+                },
+                None => dropped.push(key.clone())
+            }
+        }
+        for key in dropped {
+            self.cells.remove(&key);
+        }
+        Ok(state)
+    }
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Persistence::new()
+    }
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PersistError::Encode { ref key, ref error } => write!(f, "can not encode value of \"{}\": {}", key, error),
+            PersistError::Decode { ref key, ref error } => write!(f, "can not decode value of \"{}\": {}", key, error)
+        }
+    }
+}
+
+impl error::Error for PersistError {}
@@ -1,24 +1,51 @@
+use sodium::CallbackSendMode;
 use sodium::Cell;
 use sodium::CellLoop;
 use sodium::CellSink;
+#[cfg(feature = "persist")]
+use sodium::CellState;
+use sodium::Clock;
+use sodium::GraphDump;
+#[cfg(feature = "persist")]
+use sodium::IsCell;
 use sodium::IsLambda0;
+use sodium::LeakReport;
 use sodium::MemoLazy;
+#[cfg(feature = "persist")]
+use sodium::PersistError;
+use sodium::SodiumError;
 use sodium::Stream;
 use sodium::StreamLoop;
 use sodium::StreamSink;
+use sodium::TransactionObserver;
+use sodium::TimerSystem;
 use sodium::gc::Finalize;
 use sodium::gc::GcCtx;
 use sodium::gc::Trace;
 use sodium::impl_;
+#[cfg(feature = "persist")]
+use sodium::persist::Persistence;
+#[cfg(feature = "persist")]
+use serde::Serialize;
+#[cfg(feature = "persist")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "persist")]
+use std::cell::RefCell;
+#[cfg(feature = "persist")]
+use std::rc::Rc;
 
 pub struct SodiumCtx {
-    impl_: impl_::SodiumCtx
+    pub impl_: impl_::SodiumCtx,
+    #[cfg(feature = "persist")]
+    persistence: Rc<RefCell<Persistence>>
 }
 
 impl SodiumCtx {
     pub fn new() -> SodiumCtx {
         SodiumCtx {
-            impl_: impl_::SodiumCtx::new()
+            impl_: impl_::SodiumCtx::new(),
+            #[cfg(feature = "persist")]
+            persistence: Rc::new(RefCell::new(Persistence::new()))
         }
     }
 
@@ -68,6 +95,18 @@ impl SodiumCtx {
         }
     }
 
+    pub fn new_timer_system<CLOCK: Clock + 'static>(&self, clock: CLOCK) -> TimerSystem {
+        TimerSystem {
+            impl_: impl_::TimerSystem::new(&self.impl_, clock)
+        }
+    }
+
+    /// Unique among the contexts created by this process. Objects from
+    /// different contexts can not be combined, which debug builds check.
+    pub fn id(&self) -> u64 {
+        self.impl_.id()
+    }
+
     pub fn gc_ctx(&self) -> GcCtx {
         self.impl_.gc_ctx()
     }
@@ -77,19 +116,116 @@ impl SodiumCtx {
         self.impl_.transaction(|| code(&sodium_ctx))
     }
 
+    /// Like `transaction`, but a panic inside the transaction is returned
+    /// as `SodiumError::Panicked`. The context stays usable afterwards, but
+    /// the transaction is not rolled back: if the panic happens while
+    /// propagating, cells updated before it keep their new values.
+    pub fn try_transaction<A,CODE:FnOnce(&SodiumCtx)->A>(&self, code: CODE) -> Result<A,SodiumError> {
+        let sodium_ctx = self.clone();
+        self.impl_.try_transaction(|| code(&sodium_ctx))
+    }
+
     pub fn post<F: FnMut() + 'static>(&self, f: F) {
         self.impl_.post(f);
     }
 
+    pub fn callback_send_mode(&self) -> CallbackSendMode {
+        self.impl_.callback_send_mode()
+    }
+
+    pub fn set_callback_send_mode(&self, mode: CallbackSendMode) {
+        self.impl_.set_callback_send_mode(mode);
+    }
+
+    /// Installs an observer of transaction internals. Returns an id for
+    /// `remove_transaction_observer`.
+    pub fn add_transaction_observer<OBSERVER: TransactionObserver + 'static>(&self, observer: OBSERVER) -> u32 {
+        self.impl_.add_transaction_observer(observer)
+    }
+
+    pub fn remove_transaction_observer(&self, id: u32) {
+        self.impl_.remove_transaction_observer(id);
+    }
+
+    /// Records the nodes created from now on, so `dump_graph` and
+    /// `leak_report` can list them. Off by default.
+    pub fn set_node_tracking(&self, enabled: bool) {
+        self.impl_.set_node_tracking(enabled);
+    }
+
+    /// The live nodes created while node tracking was on.
+    pub fn dump_graph(&self) -> GraphDump {
+        self.impl_.dump_graph()
+    }
+
+    /// Lists the nodes that are still alive, and for each what keeps it
+    /// alive. Useful when `node_count` is not 0 after everything should have
+    /// been dropped. Only nodes created while node tracking was on are
+    /// listed.
+    pub fn leak_report(&self) -> LeakReport {
+        self.impl_.leak_report()
+    }
+
+    pub fn run_pending(&self) -> usize {
+        self.impl_.run_pending()
+    }
+
+    pub fn set_pending_notifier<F: Fn() + Send + Sync + 'static>(&self, notifier: F) {
+        self.impl_.set_pending_notifier(notifier);
+    }
+
     pub fn node_count(&self) -> u32 {
         self.impl_.node_count()
     }
+
+    /// Sets the values that `restored_lazy` and `new_persistent_cell_sink`
+    /// start from. Call it before building the graph.
+    #[cfg(feature = "persist")]
+    pub fn load_state(&self, state: CellState) {
+        self.persistence.borrow_mut().load(state);
+    }
+
+    /// The value saved under key by the state given to `load_state`, or
+    /// `default` when there is none. Use it as the initial value of
+    /// `hold_lazy`, `accum_lazy` or `collect_lazy`, then register the result
+    /// with `persist_cell`.
+    #[cfg(feature = "persist")]
+    pub fn restored_lazy<A: Clone + Trace + Finalize + DeserializeOwned + 'static>(&self, key: &str, default: MemoLazy<A>) -> Result<MemoLazy<A>,PersistError> {
+        self.persistence.borrow().restore(&mut self.gc_ctx(), key, default)
+    }
+
+    /// Includes the cell's value in `save_state` under key, for as long as
+    /// the cell is alive.
+    #[cfg(feature = "persist")]
+    pub fn persist_cell<A: Clone + Trace + Finalize + Serialize + 'static,CA: IsCell<A>>(&self, key: &str, ca: &CA) {
+        self.persistence.borrow_mut().register(key, &ca.to_cell());
+    }
+
+    /// A cell sink starting from the value restored for key, which is
+    /// registered with `persist_cell`.
+    #[cfg(feature = "persist")]
+    pub fn new_persistent_cell_sink<A: Clone + Trace + Finalize + Serialize + DeserializeOwned + 'static>(&self, key: &str, default: A) -> Result<CellSink<A>,PersistError> {
+        let value = self.restored_lazy(key, self.new_lazy(move || default.clone()))?;
+        let sink = CellSink {
+            impl_: impl_::CellSink::new_lazy(&self.impl_, value)
+        };
+        self.persist_cell(key, &sink);
+        Ok(sink)
+    }
+
+    /// Samples every registered cell in one transaction.
+    #[cfg(feature = "persist")]
+    pub fn save_state(&self) -> Result<CellState,PersistError> {
+        self.impl_.transaction(|| self.persistence.borrow_mut().save())
+    }
 }
 
 impl Clone for SodiumCtx {
     fn clone(&self) -> Self {
         SodiumCtx {
-            impl_: self.impl_.clone()
+            impl_: self.impl_.clone(),
+            #[cfg(feature = "persist")]
+            persistence: self.persistence.clone()
         }
     }
 }
@@ -0,0 +1,234 @@
+use sodium::Cell;
+use sodium::CellSink;
+use sodium::SodiumCtx;
+use sodium::Stream;
+use sodium::StreamSink;
+use sodium::gc::Finalize;
+use sodium::gc::Trace;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+
+type Job = Box<dyn FnOnce(&SodiumCtx) + Send>;
+
+enum Message {
+    Run(Job),
+    Stop
+}
+
+thread_local! {
+    static SINKS: RefCell<HashMap<usize,Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_SINK_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Owns a `SodiumCtx` on a dedicated thread. Every job submitted through a
+/// `SodiumThreadHandle` runs in its own transaction on that thread, so
+/// transactions are serialized no matter which thread they come from.
+pub struct SodiumThread {
+    handle: SodiumThreadHandle,
+    join_handle_op: Option<thread::JoinHandle<()>>
+}
+
+pub struct SodiumThreadHandle {
+    sender: mpsc::Sender<Message>,
+    thread_id: thread::ThreadId
+}
+
+pub struct ThreadStreamSink<A> {
+    sink: Arc<RemoteSink>,
+    phantom: PhantomData<fn(A)>
+}
+
+pub struct ThreadCellSink<A> {
+    sink: Arc<RemoteSink>,
+    phantom: PhantomData<fn(A)>
+}
+
+struct RemoteSink {
+    id: usize,
+    thread: SodiumThreadHandle
+}
+
+impl SodiumThread {
+    pub fn spawn() -> SodiumThread {
+        let (sender, receiver) = mpsc::channel();
+        let join_handle = thread::spawn(move || {
+            let sodium_ctx = SodiumCtx::new();
+            for message in receiver {
+                match message {
+                    Message::Run(job) => sodium_ctx.transaction(|sodium_ctx| job(sodium_ctx)),
+                    Message::Stop => break
+                }
+            }
+            SINKS.with(|sinks| sinks.borrow_mut().clear());
+        });
+        SodiumThread {
+            handle: SodiumThreadHandle {
+                sender,
+                thread_id: join_handle.thread().id()
+            },
+            join_handle_op: Some(join_handle)
+        }
+    }
+
+    pub fn handle(&self) -> SodiumThreadHandle {
+        self.handle.clone()
+    }
+}
+
+impl Drop for SodiumThread {
+    fn drop(&mut self) {
+        let _ = self.handle.sender.send(Message::Stop);
+        if let Some(join_handle) = self.join_handle_op.take() {
+            let _ = join_handle.join();
+        }
+    }
+}
+
+impl SodiumThreadHandle {
+    pub fn is_sodium_thread(&self) -> bool {
+        thread::current().id() == self.thread_id
+    }
+
+    /// Queues `code` to run in its own transaction on the sodium thread and
+    /// returns immediately. Jobs submitted after the `SodiumThread` has been
+    /// dropped are discarded.
+    pub fn transaction<CODE: FnOnce(&SodiumCtx) + Send + 'static>(&self, code: CODE) {
+        let _ = self.sender.send(Message::Run(Box::new(code)));
+    }
+
+    /// Runs `code` in its own transaction on the sodium thread and waits for
+    /// the result. Panics when called from the sodium thread itself.
+    pub fn transaction_sync<A: Send + 'static, CODE: FnOnce(&SodiumCtx)->A + Send + 'static>(&self, code: CODE) -> A {
+        if self.is_sodium_thread() {
+            panic!("SodiumThreadHandle::transaction_sync can not be called from the sodium thread, use SodiumThreadHandle::transaction instead.");
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.transaction(move |sodium_ctx| {
+            let _ = sender.send(code(sodium_ctx));
+        });
+        receiver.recv().expect("SodiumThread stopped before the transaction completed.")
+    }
+
+    pub fn new_stream_sink<A: Clone + Trace + Finalize + Send + 'static>(&self) -> ThreadStreamSink<A> {
+        let id = NEXT_SINK_ID.fetch_add(1, Ordering::SeqCst);
+        self.transaction(move |sodium_ctx| {
+            let sink: StreamSink<A> = sodium_ctx.new_stream_sink();
+            SINKS.with(|sinks| sinks.borrow_mut().insert(id, Box::new(sink)));
+        });
+        ThreadStreamSink {
+            sink: Arc::new(RemoteSink { id, thread: self.clone() }),
+            phantom: PhantomData
+        }
+    }
+
+    pub fn new_cell_sink<A: Clone + Trace + Finalize + Send + 'static>(&self, value: A) -> ThreadCellSink<A> {
+        let id = NEXT_SINK_ID.fetch_add(1, Ordering::SeqCst);
+        self.transaction(move |sodium_ctx| {
+            let sink: CellSink<A> = sodium_ctx.new_cell_sink(value);
+            SINKS.with(|sinks| sinks.borrow_mut().insert(id, Box::new(sink)));
+        });
+        ThreadCellSink {
+            sink: Arc::new(RemoteSink { id, thread: self.clone() }),
+            phantom: PhantomData
+        }
+    }
+}
+
+impl Clone for SodiumThreadHandle {
+    fn clone(&self) -> Self {
+        SodiumThreadHandle {
+            sender: self.sender.clone(),
+            thread_id: self.thread_id
+        }
+    }
+}
+
+impl RemoteSink {
+    fn local<S: Clone + 'static>(&self) -> S {
+        if !self.thread.is_sodium_thread() {
+            panic!("Thread sinks can only be accessed locally from their sodium thread.");
+        }
+        SINKS.with(|sinks| {
+            sinks
+                .borrow()
+                .get(&self.id)
+                .and_then(|sink| sink.downcast_ref::<S>())
+                .cloned()
+                .expect("Thread sink is no longer registered with its sodium thread.")
+        })
+    }
+}
+
+impl Drop for RemoteSink {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.thread.transaction(move |_sodium_ctx| {
+            let sink_op = SINKS.with(|sinks| sinks.borrow_mut().remove(&id));
+            drop(sink_op);
+        });
+    }
+}
+
+impl<A: Clone + Trace + Finalize + Send + 'static> ThreadStreamSink<A> {
+    pub fn send(&self, a: A) {
+        let sink = self.sink.clone();
+        self.sink.thread.transaction(move |_sodium_ctx| {
+            sink.local::<StreamSink<A>>().send(&a);
+        });
+    }
+
+    /// The underlying sink. Only available on the sodium thread, e.g. inside
+    /// a closure passed to `SodiumThreadHandle::transaction`.
+    pub fn to_stream_sink(&self) -> StreamSink<A> {
+        self.sink.local()
+    }
+
+    pub fn to_stream(&self) -> Stream<A> {
+        self.to_stream_sink().to_stream()
+    }
+}
+
+impl<A: Clone + Trace + Finalize + Send + 'static> ThreadCellSink<A> {
+    pub fn send(&self, a: A) {
+        let sink = self.sink.clone();
+        self.sink.thread.transaction(move |_sodium_ctx| {
+            sink.local::<CellSink<A>>().send(&a);
+        });
+    }
+
+    /// The underlying sink. Only available on the sodium thread, e.g. inside
+    /// a closure passed to `SodiumThreadHandle::transaction`.
+    pub fn to_cell_sink(&self) -> CellSink<A> {
+        self.sink.local()
+    }
+
+    pub fn to_cell(&self) -> Cell<A> {
+        self.to_cell_sink().to_cell()
+    }
+}
+
+impl<A> Clone for ThreadStreamSink<A> {
+    fn clone(&self) -> Self {
+        ThreadStreamSink {
+            sink: self.sink.clone(),
+            phantom: PhantomData
+        }
+    }
+}
+
+impl<A> Clone for ThreadCellSink<A> {
+    fn clone(&self) -> Self {
+        ThreadCellSink {
+            sink: self.sink.clone(),
+            phantom: PhantomData
+        }
+    }
+}
@@ -8,12 +8,15 @@ use sodium::IsLambda3;
 use sodium::IsLambda4;
 use sodium::IsLambda5;
 use sodium::IsLambda6;
+use sodium::Lambda;
 use sodium::Listener;
 use sodium::MemoLazy;
+use sodium::TimerSystem;
 use sodium::gc::Finalize;
 use sodium::gc::GcDep;
 use sodium::gc::Trace;
 use sodium::impl_;
+use std::time::Duration;
 
 pub struct Stream<A> {
     pub impl_: impl_::Stream<A>
@@ -27,6 +30,15 @@ impl<A: Clone + Trace + Finalize + 'static> Stream<Option<A>> {
     }
 }
 
+impl<A: Clone + Trace + Finalize + PartialEq + 'static> Stream<A> {
+    /// Drops events equal to the last one passed on.
+    pub fn calm(&self) -> Stream<A> {
+        Stream {
+            impl_: self.impl_.calm()
+        }
+    }
+}
+
 impl<A: Clone + Trace + Finalize + 'static> Stream<A> {
 
     pub fn to_dep(&self) -> Dep {
@@ -127,6 +139,85 @@ impl<A: Clone + Trace + Finalize + 'static> Stream<A> {
         }
     }
 
+    /// Samples all of `cells` whenever this stream fires.
+    pub fn snapshot_all<B,CB:IsCell<B>>(&self, cells: Vec<CB>) -> Stream<Vec<B>> where B: Trace + Finalize + Clone + 'static {
+        Stream {
+            impl_: self.impl_.snapshot_all(cells.iter().map(|c| c.to_cell().impl_).collect())
+        }
+    }
+
+    /// Switches to the stream `f` returns for each event. As with
+    /// `Cell::switch_s`, the switch takes effect at the end of the
+    /// transaction, so an event from the old inner stream in the same
+    /// transaction is still passed on and one from the new stream is not.
+    pub fn switch_map<B,SB,F>(&self, f: F) -> Stream<B>
+        where B: Trace + Finalize + Clone + 'static,
+              SB: IsStream<B> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,SB> + 'static
+    {
+        Stream {
+            impl_: self.impl_.switch_map(Stream::_inner_stream(f))
+        }
+    }
+
+    /// Like `switch_map`, but events are ignored while the inner stream is
+    /// active. The inner stream passes on each `Some` and ends with a `None`.
+    pub fn exhaust_map<B,SB,F>(&self, f: F) -> Stream<B>
+        where B: Trace + Finalize + Clone + 'static,
+              SB: IsStream<Option<B>> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,SB> + 'static
+    {
+        Stream {
+            impl_: self.impl_.exhaust_map(Stream::_inner_stream(f))
+        }
+    }
+
+    fn _inner_stream<B,SB,F>(f: F) -> Lambda<impl Fn(&A)->impl_::Stream<B>>
+        where B: Trace + Finalize + Clone + 'static,
+              SB: IsStream<B> + Trace + Finalize + Clone + 'static,
+              F: IsLambda1<A,SB> + 'static
+    {
+        let deps = f.deps();
+        Lambda::new(move |a: &A| f.apply(a).to_stream().impl_, deps)
+    }
+
+    /// Merges whichever streams `csa` holds, combining simultaneous events
+    /// with `f` in the order of the `Vec`. Like `Cell::switch_s`, a new set of
+    /// streams takes effect at the end of the transaction.
+    pub fn merge_all<SA,CSA,FN>(csa: CSA, f: FN) -> Stream<A>
+        where SA: IsStream<A> + Trace + Finalize + Clone + 'static,
+              CSA: IsCell<Vec<SA>>,
+              FN: Fn(&A,&A)->A + 'static
+    {
+        Stream {
+            impl_: impl_::Stream::merge_all(csa.to_cell().impl_.map(|streams: &Vec<SA>| streams.iter().map(|sa| sa.to_stream().impl_).collect()), f)
+        }
+    }
+
+    pub fn debounce(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<A> {
+        Stream {
+            impl_: timer_system.impl_.debounce(self.impl_.clone(), duration)
+        }
+    }
+
+    pub fn throttle(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<A> {
+        Stream {
+            impl_: timer_system.impl_.throttle(self.impl_.clone(), duration)
+        }
+    }
+
+    pub fn delay(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<A> {
+        Stream {
+            impl_: timer_system.impl_.delay(self.impl_.clone(), duration)
+        }
+    }
+
+    pub fn timeout(&self, timer_system: &TimerSystem, duration: Duration) -> Stream<Duration> {
+        Stream {
+            impl_: timer_system.impl_.timeout(self.impl_.clone(), duration)
+        }
+    }
+
     pub fn listen<CALLBACK:FnMut(&A)+'static>(
         &self,
         callback: CALLBACK
@@ -1,4 +1,5 @@
 use sodium::IsStream;
+use sodium::SodiumError;
 use sodium::Stream;
 use sodium::gc::Finalize;
 use sodium::gc::Trace;
@@ -14,6 +15,10 @@ impl<A: Trace + Finalize + Clone + 'static> StreamLoop<A> {
         self.impl_.loop_(sa.to_stream().impl_);
     }
 
+    pub fn try_loop<SA:IsStream<A>>(&self, sa: SA) -> Result<(),SodiumError> {
+        self.impl_.try_loop(sa.to_stream().impl_)
+    }
+
     pub fn to_stream(&self) -> Stream<A> {
         Stream {
             impl_: self.impl_.to_stream()
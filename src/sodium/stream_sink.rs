@@ -1,3 +1,5 @@
+use sodium::CallbackSendMode;
+use sodium::SinkSender;
 use sodium::Stream;
 use sodium::gc::Finalize;
 use sodium::gc::GcDep;
@@ -13,6 +15,18 @@ impl<A: Clone + Trace + Finalize + 'static> StreamSink<A> {
         self.impl_.send(a.clone());
     }
 
+    pub fn callback_send_mode(&self) -> CallbackSendMode {
+        self.impl_.callback_send_mode()
+    }
+
+    pub fn set_callback_send_mode(&self, mode: CallbackSendMode) {
+        self.impl_.set_callback_send_mode(mode);
+    }
+
+    pub fn sender(&self) -> SinkSender<A> where A: Send {
+        self.impl_.sender()
+    }
+
     pub fn to_stream(&self) -> Stream<A> {
         Stream {
             impl_: self.impl_.to_stream()
@@ -0,0 +1,308 @@
+//! Marble-diagram tests. Each character of a marble is one frame, and each
+//! frame runs as one transaction:
+//!
+//! * `-` is a frame with no events.
+//! * Any other character is an event, whose value is looked up in the table
+//!   passed along with the marble.
+//! * `(cd)` puts several events in the same frame.
+//! * `|` ends the marble. An expected marble without it only checks the
+//!   frames it covers, one with it also checks that nothing happens after.
+//!
+//! Whitespace is ignored, so marbles can be lined up with each other.
+//!
+//! ```
+//! use sodium_rust::sodium::IsStream;
+//! use sodium_rust::sodium::testing::run_marbles;
+//!
+//! run_marbles(|m| {
+//!     let s = m.stream_sink("a-b-|", &[('a', 1), ('b', 2)]);
+//!     let out = s.map(|a: &i32| *a * 10);
+//!     m.expect_stream(&out, "x-y-|", &[('x', 10), ('y', 20)]);
+//! });
+//! ```
+
+use sodium::CellSink;
+use sodium::IsCell;
+use sodium::IsStream;
+use sodium::Listener;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use sodium::gc::Finalize;
+use sodium::gc::Trace;
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+struct Marble {
+    frames: Vec<Vec<char>>,
+    complete: bool
+}
+
+impl Marble {
+    fn parse(marble: &str) -> Marble {
+        let mut frames = Vec::new();
+        let mut group_op: Option<Vec<char>> = None;
+        let mut complete = false;
+        for c in marble.chars() {
+            if c.is_whitespace() {
+                continue;
+            }
+            if complete {
+                panic!("marble \"{}\" has frames after '|'", marble);
+            }
+            match (c, group_op.take()) {
+                ('(', None) => group_op = Some(Vec::new()),
+                (')', Some(group)) => frames.push(group),
+                ('(', Some(_)) | ('|', Some(_)) | ('-', Some(_)) =>
+                    panic!("marble \"{}\" has '{}' inside a group", marble, c),
+                (')', None) => panic!("marble \"{}\" has an unopened ')'", marble),
+                (c, Some(mut group)) => {
+                    group.push(c);
+                    group_op = Some(group);
+                },
+                ('-', None) => frames.push(Vec::new()),
+                ('|', None) => {
+                    frames.push(Vec::new());
+                    complete = true;
+                },
+                (c, None) => frames.push(vec![c])
+            }
+        }
+        if group_op.is_some() {
+            panic!("marble \"{}\" has an unclosed '('", marble);
+        }
+        Marble { frames, complete }
+    }
+}
+
+fn value_of<A: Clone>(marble: &str, values: &[(char,A)], c: char) -> A {
+    values.iter()
+        .find(|&&(c2, _)| c2 == c)
+        .map(|(_, a)| a.clone())
+        .unwrap_or_else(|| panic!("marble \"{}\" uses '{}', which has no value", marble, c))
+}
+
+struct Input {
+    frames: Vec<Vec<char>>,
+    send: Box<dyn Fn(char)>
+}
+
+trait Expectation {
+    fn frame_count(&self) -> usize;
+
+    fn check(&self, frame_count: usize) -> Result<(),String>;
+
+    fn unlisten(&self);
+}
+
+struct Expected<A> {
+    desc: String,
+    marble: Marble,
+    values: Vec<(char,A)>,
+    actual: Rc<RefCell<Vec<(usize,A)>>>,
+    listener: Listener
+}
+
+impl<A: Clone + PartialEq + Debug> Expected<A> {
+    fn render(&self, frames: &[Vec<A>]) -> String {
+        let mut result = String::new();
+        for frame in frames {
+            let cs: Vec<char> = frame.iter()
+                .map(|a| self.values.iter().find(|&(_, a2)| a2 == a).map(|&(c, _)| c).unwrap_or('?'))
+                .collect();
+            match cs.len() {
+                0 => result.push('-'),
+                1 => result.push(cs[0]),
+                _ => {
+                    result.push('(');
+                    result.extend(cs);
+                    result.push(')');
+                }
+            }
+        }
+        result
+    }
+}
+
+impl<A: Clone + PartialEq + Debug> Expectation for Expected<A> {
+    fn frame_count(&self) -> usize {
+        self.marble.frames.len()
+    }
+
+    fn check(&self, frame_count: usize) -> Result<(),String> {
+        let mut actual_frames: Vec<Vec<A>> = vec![Vec::new(); frame_count];
+        for &(frame, ref a) in self.actual.borrow().iter() {
+            actual_frames[frame].push(a.clone());
+        }
+        let checked = if self.marble.complete { frame_count } else { self.marble.frames.len() };
+        let expected_frames: Vec<Vec<A>> =
+            (0..checked)
+                .map(|frame| match self.marble.frames.get(frame) {
+                    Some(cs) => cs.iter().map(|&c| value_of(&self.desc, &self.values, c)).collect(),
+                    None => Vec::new()
+                })
+                .collect();
+        if expected_frames[..] == actual_frames[..checked] {
+            return Ok(());
+        }
+        Err(format!(
+            "{}\n  expected: {}\n    actual: {}\n    values: {:?}",
+            self.desc,
+            self.render(&expected_frames),
+            self.render(&actual_frames),
+            self.actual.borrow().iter().map(|(_, a)| a.clone()).collect::<Vec<A>>()
+        ))
+    }
+
+    fn unlisten(&self) {
+        self.listener.unlisten();
+    }
+}
+
+/// Collects the sinks and expectations of a marble test. See `run_marbles`.
+pub struct MarbleTest {
+    sodium_ctx: SodiumCtx,
+    frame: Rc<RefCell<usize>>,
+    inputs: Vec<Input>,
+    expectations: Vec<Box<dyn Expectation>>
+}
+
+impl MarbleTest {
+    pub fn sodium_ctx(&self) -> &SodiumCtx {
+        &self.sodium_ctx
+    }
+
+    /// A new stream sink that is sent the marble's events.
+    pub fn stream_sink<A: Clone + Trace + Finalize + 'static>(&mut self, marble: &str, values: &[(char,A)]) -> StreamSink<A> {
+        let sink = self.sodium_ctx.new_stream_sink();
+        self.send_stream(&sink, marble, values);
+        sink
+    }
+
+    /// Sends the marble's events to an existing stream sink, for example one
+    /// made with a coalescer so that groups can be tested.
+    pub fn send_stream<A: Clone + Trace + Finalize + 'static>(&mut self, sink: &StreamSink<A>, marble: &str, values: &[(char,A)]) {
+        let sink = sink.clone();
+        self.add_input(marble, values, move |a: &A| sink.send(a));
+    }
+
+    /// A new cell sink, holding `initial` until the marble's events are sent
+    /// to it.
+    pub fn cell_sink<A: Clone + Trace + Finalize + 'static>(&mut self, initial: A, marble: &str, values: &[(char,A)]) -> CellSink<A> {
+        let sink = self.sodium_ctx.new_cell_sink(initial);
+        self.send_cell(&sink, marble, values);
+        sink
+    }
+
+    pub fn send_cell<A: Clone + Trace + Finalize + 'static>(&mut self, sink: &CellSink<A>, marble: &str, values: &[(char,A)]) {
+        let sink = sink.clone();
+        self.add_input(marble, values, move |a: &A| sink.send(a));
+    }
+
+    /// Expects the stream to fire as the marble describes.
+    pub fn expect_stream<A: Clone + Trace + Finalize + PartialEq + Debug + 'static,SA: IsStream<A>>(&mut self, sa: &SA, marble: &str, values: &[(char,A)]) {
+        let actual = Rc::new(RefCell::new(Vec::new()));
+        let listener;
+        {
+            let actual = actual.clone();
+            let frame = self.frame.clone();
+            listener = sa.listen(move |a: &A| actual.borrow_mut().push((*frame.borrow(), a.clone())));
+        }
+        self.add_expectation(marble, values, actual, listener);
+    }
+
+    /// Expects the cell to take on values as the marble describes. The value
+    /// the cell holds when this is called counts as an event of frame 0, so a
+    /// cell that is also updated in frame 0 starts with a group.
+    pub fn expect_cell<A: Clone + Trace + Finalize + PartialEq + Debug + 'static,CA: IsCell<A>>(&mut self, ca: &CA, marble: &str, values: &[(char,A)]) {
+        let actual = Rc::new(RefCell::new(Vec::new()));
+        let listener;
+        {
+            let actual = actual.clone();
+            let frame = self.frame.clone();
+            listener = ca.listen(move |a: &A| actual.borrow_mut().push((*frame.borrow(), a.clone())));
+        }
+        self.add_expectation(marble, values, actual, listener);
+    }
+
+    fn add_input<A: Clone + 'static,SEND: Fn(&A) + 'static>(&mut self, marble: &str, values: &[(char,A)], send: SEND) {
+        let parsed = Marble::parse(marble);
+        for cs in &parsed.frames {
+            for &c in cs {
+                value_of(marble, values, c);
+            }
+        }
+        let marble = marble.to_string();
+        let values = values.to_vec();
+        self.inputs.push(Input {
+            frames: parsed.frames,
+            send: Box::new(move |c: char| send(&value_of(&marble, &values, c)))
+        });
+    }
+
+    fn add_expectation<A: Clone + PartialEq + Debug + 'static>(&mut self, marble: &str, values: &[(char,A)], actual: Rc<RefCell<Vec<(usize,A)>>>, listener: Listener) {
+        self.expectations.push(Box::new(Expected {
+            desc: marble.to_string(),
+            marble: Marble::parse(marble),
+            values: values.to_vec(),
+            actual,
+            listener
+        }));
+    }
+
+    fn run(self) -> Vec<String> {
+        let frame_count =
+            self.inputs.iter().map(|input| input.frames.len())
+                .chain(self.expectations.iter().map(|expectation| expectation.frame_count()))
+                .max()
+                .unwrap_or(0);
+        for frame in 0..frame_count {
+            *self.frame.borrow_mut() = frame;
+            let sends: Vec<(&Input,char)> =
+                self.inputs.iter()
+                    .flat_map(|input| input.frames.get(frame).into_iter().flat_map(move |cs| cs.iter().map(move |&c| (input, c))))
+                    .collect();
+            if !sends.is_empty() {
+                self.sodium_ctx.transaction(|_| {
+                    for (input, c) in sends {
+                        (input.send)(c);
+                    }
+                });
+            }
+        }
+        let mut failures = Vec::new();
+        for expectation in &self.expectations {
+            expectation.unlisten();
+            if let Err(failure) = expectation.check(frame_count) {
+                failures.push(failure);
+            }
+        }
+        failures
+    }
+}
+
+/// Builds a graph with `setup`, runs the marbles registered on the
+/// `MarbleTest`, and panics if any expectation was not met or if any nodes
+/// are left once everything has been dropped.
+pub fn run_marbles<SETUP: FnOnce(&mut MarbleTest)>(setup: SETUP) {
+    let sodium_ctx = SodiumCtx::new();
+    sodium_ctx.set_node_tracking(true);
+    let mut failures;
+    {
+        let mut test = MarbleTest {
+            sodium_ctx: sodium_ctx.clone(),
+            frame: Rc::new(RefCell::new(0)),
+            inputs: Vec::new(),
+            expectations: Vec::new()
+        };
+        setup(&mut test);
+        failures = test.run();
+    }
+    let node_count = sodium_ctx.node_count();
+    if node_count != 0 {
+        failures.push(format!("memory leak detected, {}", sodium_ctx.leak_report()));
+    }
+    if !failures.is_empty() {
+        panic!("marble test failed:\n{}", failures.join("\n"));
+    }
+}
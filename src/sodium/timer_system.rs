@@ -0,0 +1,49 @@
+use sodium::Cell;
+use sodium::IsCell;
+use sodium::Stream;
+use sodium::impl_;
+use std::time::Duration;
+
+pub struct TimerSystem {
+    pub impl_: impl_::TimerSystem
+}
+
+impl TimerSystem {
+    pub fn now(&self) -> Duration {
+        self.impl_.now()
+    }
+
+    pub fn time(&self) -> Cell<Duration> {
+        Cell {
+            impl_: self.impl_.time()
+        }
+    }
+
+    pub fn at<CA:IsCell<Option<Duration>>>(&self, c_time: CA) -> Stream<Duration> {
+        Stream {
+            impl_: self.impl_.at(c_time.to_cell().impl_)
+        }
+    }
+
+    pub fn periodic(&self, duration: Duration) -> Stream<Duration> {
+        Stream {
+            impl_: self.impl_.periodic(duration)
+        }
+    }
+
+    pub fn next_alarm_time(&self) -> Option<Duration> {
+        self.impl_.next_alarm_time()
+    }
+
+    pub fn run_timers(&self) {
+        self.impl_.run_timers();
+    }
+}
+
+impl Clone for TimerSystem {
+    fn clone(&self) -> Self {
+        TimerSystem {
+            impl_: self.impl_.clone()
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use sodium::HookKind;
+use sodium::TransactionObserver;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// A `TransactionObserver` that times every transaction. Install a clone of
+/// it with `SodiumCtx::add_transaction_observer` and read the reports back
+/// from the original.
+pub struct TransactionRecorder {
+    data: Rc<RefCell<TransactionRecorderData>>
+}
+
+struct TransactionRecorderData {
+    reports: Vec<TransactionReport>,
+    in_progress: Vec<InProgress>
+}
+
+struct InProgress {
+    start: Instant,
+    update_start_op: Option<Instant>,
+    hook_starts: Vec<Instant>,
+    report: TransactionReport
+}
+
+#[derive(Clone,Debug)]
+pub struct TransactionReport {
+    pub duration: Duration,
+    /// Ids of the nodes marked dirty, in the order they were marked.
+    pub marked_dirty: Vec<u32>,
+    /// Node updates in the order they ran.
+    pub node_updates: Vec<NodeUpdateReport>,
+    pub hooks: Vec<HookReport>
+}
+
+#[derive(Clone,Debug)]
+pub struct NodeUpdateReport {
+    pub id: u32,
+    pub desc: String,
+    pub rank: u32,
+    pub changed: bool,
+    pub duration: Duration
+}
+
+#[derive(Clone,Debug)]
+pub struct HookReport {
+    pub kind: HookKind,
+    pub duration: Duration
+}
+
+impl TransactionRecorder {
+    pub fn new() -> TransactionRecorder {
+        TransactionRecorder {
+            data: Rc::new(RefCell::new(TransactionRecorderData {
+                reports: Vec::new(),
+                in_progress: Vec::new()
+            }))
+        }
+    }
+
+    /// Reports of the completed transactions, oldest first.
+    pub fn reports(&self) -> Vec<TransactionReport> {
+        self.data.borrow().reports.clone()
+    }
+
+    pub fn clear(&self) {
+        self.data.borrow_mut().reports.clear();
+    }
+
+    fn with_current<F: FnOnce(&mut InProgress)>(&self, f: F) {
+        let mut data = self.data.borrow_mut();
+        if let Some(current) = data.in_progress.last_mut() {
+            f(current);
+        }
+    }
+}
+
+impl Default for TransactionRecorder {
+    fn default() -> Self {
+        TransactionRecorder::new()
+    }
+}
+
+impl Clone for TransactionRecorder {
+    fn clone(&self) -> Self {
+        TransactionRecorder {
+            data: self.data.clone()
+        }
+    }
+}
+
+impl TransactionObserver for TransactionRecorder {
+    fn transaction_start(&mut self) {
+        self.data.borrow_mut().in_progress.push(InProgress {
+            start: Instant::now(),
+            update_start_op: None,
+            hook_starts: Vec::new(),
+            report: TransactionReport {
+                duration: Duration::from_secs(0),
+                marked_dirty: Vec::new(),
+                node_updates: Vec::new(),
+                hooks: Vec::new()
+            }
+        });
+    }
+
+    fn transaction_end(&mut self) {
+        let mut data = self.data.borrow_mut();
+        if let Some(mut current) = data.in_progress.pop() {
+            current.report.duration = current.start.elapsed();
+            data.reports.push(current.report);
+        }
+    }
+
+    fn node_marked_dirty(&mut self, id: u32, _desc: &str, _rank: u32) {
+        self.with_current(|current| current.report.marked_dirty.push(id));
+    }
+
+    fn node_update_start(&mut self, _id: u32, _desc: &str, _rank: u32) {
+        self.with_current(|current| current.update_start_op = Some(Instant::now()));
+    }
+
+    fn node_update_end(&mut self, id: u32, desc: &str, rank: u32, changed: bool) {
+        self.with_current(|current| {
+            let duration = current.update_start_op.take().map(|start| start.elapsed()).unwrap_or_default();
+            current.report.node_updates.push(NodeUpdateReport {
+                id,
+                desc: desc.to_string(),
+                rank,
+                changed,
+                duration
+            });
+        });
+    }
+
+    fn hook_start(&mut self, _kind: HookKind) {
+        self.with_current(|current| current.hook_starts.push(Instant::now()));
+    }
+
+    fn hook_end(&mut self, kind: HookKind) {
+        self.with_current(|current| {
+            let duration = current.hook_starts.pop().map(|start| start.elapsed()).unwrap_or_default();
+            current.report.hooks.push(HookReport { kind, duration });
+        });
+    }
+}
+
+impl fmt::Display for TransactionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "transaction {:?}, {} dirty, {} updates, {} hooks", self.duration, self.marked_dirty.len(), self.node_updates.len(), self.hooks.len())?;
+        for update in &self.node_updates {
+            writeln!(f, "  rank {:>4} node {:>6} {:?} {}{}", update.rank, update.id, update.duration, update.desc, if update.changed { "" } else { " (unchanged)" })?;
+        }
+        for hook in &self.hooks {
+            writeln!(f, "  {:?} hook {:?}", hook.kind, hook.duration)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use sodium::CallbackSendMode;
+use sodium::IsCell;
+use sodium::IsStream;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn deferred_send_from_listen() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        sodium_ctx.set_callback_send_mode(CallbackSendMode::Defer);
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            let s2 = s.clone();
+            l = s.listen(move |a: &i32| {
+                out.borrow_mut().push(*a);
+                if *a < 3 {
+                    s2.send(&(*a + 1));
+                }
+            });
+        }
+        s.send(&1);
+        l.unlisten();
+        assert_eq!(vec![1, 2, 3], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn long_chain_of_deferred_sends() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        sodium_ctx.set_callback_send_mode(CallbackSendMode::Defer);
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let count = Rc::new(RefCell::new(0));
+        let l;
+        {
+            let count = count.clone();
+            let s2 = s.clone();
+            l = s.listen(move |a: &i32| {
+                *count.borrow_mut() += 1;
+                if *a < 10000 {
+                    s2.send(&(*a + 1));
+                }
+            });
+        }
+        s.send(&1);
+        l.unlisten();
+        assert_eq!(10000, *count.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn deferred_send_per_sink() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let c = sodium_ctx.new_cell_sink(0);
+        c.set_callback_send_mode(CallbackSendMode::Defer);
+        assert_eq!(CallbackSendMode::Panic, sodium_ctx.callback_send_mode());
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let c2 = c.clone();
+            let l1 = s.listen(move |a: &i32| c2.send(&(*a * 10)));
+            let out = out.clone();
+            let l2 = c.listen(move |a: &i32| out.borrow_mut().push(*a));
+            l = (l1, l2);
+        }
+        s.send(&1);
+        s.send(&2);
+        l.0.unlisten();
+        l.1.unlisten();
+        assert_eq!(vec![0, 10, 20], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+#[should_panic]
+fn send_from_callback_panics_by_default() {
+    let sodium_ctx = SodiumCtx::new();
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    let s2 = s.clone();
+    let _l = s.listen(move |a: &i32| s2.send(&(*a + 1)));
+    s.send(&1);
+}
@@ -4,6 +4,8 @@ use sodium::IsCell;
 use sodium::IsStream;
 use sodium::Operational;
 use sodium::SodiumCtx;
+use sodium::SodiumError;
+use sodium::StreamLoop;
 use sodium::StreamSink;
 use tests::assert_memory_freed;
 use std::cell::RefCell;
@@ -104,3 +106,72 @@ fn lift_loop() {
     }
     assert_memory_freed(sodium_ctx);
 }
+
+#[test]
+fn try_loop_and_try_sample() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let result = sodium_ctx.try_transaction(|sodium_ctx| {
+            let ca: CellLoop<i32> = sodium_ctx.new_cell_loop();
+            let doubled = ca.map(|a: &i32| *a * 2);
+            assert!(!ca.is_looped());
+            assert_eq!(Err(SodiumError::SampledBeforeLooped), doubled.try_sample());
+            assert_eq!(Ok(()), ca.try_loop(sodium_ctx.new_cell(3)));
+            assert!(ca.is_looped());
+            assert_eq!(Ok(6), doubled.try_sample());
+            assert_eq!(Err(SodiumError::AlreadyLooped), ca.try_loop(sodium_ctx.new_cell(4)));
+            let sa: StreamLoop<i32> = sodium_ctx.new_stream_loop();
+            let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+            assert_eq!(Ok(()), sa.try_loop(&s));
+            assert_eq!(Err(SodiumError::AlreadyLooped), sa.try_loop(&s));
+            doubled
+        });
+        assert_eq!(Ok(6), result.map(|doubled| doubled.sample()));
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn unlooped_loops_reported() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let result = sodium_ctx.try_transaction(|sodium_ctx| {
+            let looped: CellLoop<i32> = sodium_ctx.new_cell_loop();
+            looped.loop_(sodium_ctx.new_cell(1));
+            let unlooped: StreamLoop<i32> = sodium_ctx.new_stream_loop();
+            (looped, unlooped)
+        });
+        match result {
+            Err(SodiumError::Unlooped(ids)) => assert_eq!(1, ids.len()),
+            _ => panic!("expected SodiumError::Unlooped")
+        }
+        assert_eq!(Ok(()), sodium_ctx.try_transaction(|_| ()));
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn loops_created_while_propagating_reported() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let loops: Rc<RefCell<Vec<StreamLoop<i32>>>> = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let sodium_ctx = sodium_ctx.clone();
+            let loops = loops.clone();
+            l = s.listen(move |_: &i32| loops.borrow_mut().push(sodium_ctx.new_stream_loop()));
+        }
+        match sodium_ctx.try_transaction(|_| s.send(&1)) {
+            Err(SodiumError::Unlooped(ids)) => assert_eq!(1, ids.len()),
+            _ => panic!("expected SodiumError::Unlooped")
+        }
+        assert_eq!(Ok(()), sodium_ctx.try_transaction(|_| ()));
+        l.unlisten();
+        loops.borrow_mut().clear();
+    }
+    assert_memory_freed(sodium_ctx);
+}
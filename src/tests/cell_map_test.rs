@@ -0,0 +1,185 @@
+use sodium::CellMap;
+use sodium::CellSink;
+use sodium::MapChange;
+use sodium::MapDiff;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use sodium::TransactionObserver;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+#[test]
+fn hold_diffs_and_snapshot() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let changes: StreamSink<Vec<MapChange<i32,i32>>> = sodium_ctx.new_stream_sink();
+        let map = CellMap::hold(BTreeMap::new(), &changes);
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = map.diffs().listen(move |diffs: &Vec<MapDiff<i32,i32>>| out.borrow_mut().push(diffs.clone()));
+        }
+        changes.send(&vec![MapChange::Insert(1, 10), MapChange::Insert(2, 20)]);
+        changes.send(&vec![MapChange::Insert(1, 11), MapChange::Remove(2), MapChange::Remove(3)]);
+        changes.send(&vec![MapChange::Remove(3)]);
+        l.unlisten();
+        assert_eq!(
+            vec![
+                vec![MapDiff::Insert(1, 10), MapDiff::Insert(2, 20)],
+                vec![MapDiff::Update(1, 10, 11), MapDiff::Remove(2, 20)]
+            ],
+            *out.borrow()
+        );
+        assert_eq!(vec![(1, 11)], map.snapshot().sample().into_iter().collect::<Vec<_>>());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn map_filter_fold() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let changes: StreamSink<Vec<MapChange<i32,i32>>> = sodium_ctx.new_stream_sink();
+        let mut initial = BTreeMap::new();
+        initial.insert(1, 1);
+        initial.insert(2, 2);
+        let map = CellMap::hold(initial, &changes);
+        let calls = Rc::new(RefCell::new(0));
+        let doubled;
+        {
+            let calls = calls.clone();
+            doubled = map.map(move |v: &i32| { *calls.borrow_mut() += 1; *v * 2 });
+        }
+        let big = doubled.filter(|_k: &i32, v: &i32| *v > 4);
+        let sum = doubled.fold(0, |s: &i32, _k: &i32, v: &i32| *s + *v, |s: &i32, _k: &i32, v: &i32| *s - *v);
+        let l = big.snapshot().listen(|_: &BTreeMap<i32,i32>| {});
+        let l2 = sum.listen(|_: &i32| {});
+        assert_eq!(2, *calls.borrow());
+        changes.send(&vec![MapChange::Insert(3, 3)]);
+        assert_eq!(3, *calls.borrow());
+        assert_eq!(12, sum.sample());
+        assert_eq!(vec![(3, 6)], big.snapshot().sample().into_iter().collect::<Vec<_>>());
+        changes.send(&vec![MapChange::Insert(1, 5), MapChange::Insert(3, 0)]);
+        assert_eq!(14, sum.sample());
+        assert_eq!(vec![(1, 10)], big.snapshot().sample().into_iter().collect::<Vec<_>>());
+        changes.send(&vec![MapChange::Remove(1)]);
+        assert_eq!(4, sum.sample());
+        assert!(big.snapshot().sample().is_empty());
+        l.unlisten();
+        l2.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn flatten() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let a: CellSink<i32> = sodium_ctx.new_cell_sink(1);
+        let b: CellSink<i32> = sodium_ctx.new_cell_sink(2);
+        let changes = sodium_ctx.new_stream_sink();
+        let mut initial = BTreeMap::new();
+        initial.insert("a", a.to_cell());
+        let map = CellMap::hold(initial, &changes).flatten();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = map.diffs().listen(move |diffs: &Vec<MapDiff<&'static str,i32>>| out.borrow_mut().push(diffs.clone()));
+        }
+        a.send(&3);
+        changes.send(&vec![MapChange::Insert("b", b.to_cell())]);
+        b.send(&4);
+        changes.send(&vec![MapChange::Remove("a")]);
+        a.send(&5);
+        l.unlisten();
+        assert_eq!(
+            vec![
+                vec![MapDiff::Update("a", 1, 3)],
+                vec![MapDiff::Insert("b", 2)],
+                vec![MapDiff::Update("b", 2, 4)],
+                vec![MapDiff::Remove("a", 3)]
+            ],
+            *out.borrow()
+        );
+        assert_eq!(vec![("b", 4)], map.snapshot().sample().into_iter().collect::<Vec<_>>());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+struct UpdateCount {
+    count: Rc<RefCell<u32>>
+}
+
+impl TransactionObserver for UpdateCount {
+    fn node_update_start(&mut self, _id: u32, _desc: &str, _rank: u32) {
+        *self.count.borrow_mut() += 1;
+    }
+}
+
+/// The node updates it takes to pass on a change of one cell in a flattened
+/// map of `size` cells.
+fn flatten_updates_for_one_send(size: i32) -> u32 {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let count = Rc::new(RefCell::new(0));
+    {
+        let cells: Vec<CellSink<i32>> = (0..size).map(|i| sodium_ctx.new_cell_sink(i)).collect();
+        let changes: StreamSink<Vec<MapChange<i32,_>>> = sodium_ctx.new_stream_sink();
+        let initial = cells.iter().enumerate().map(|(i, c)| (i as i32, c.to_cell())).collect();
+        let map = CellMap::hold(initial, &changes).flatten();
+        let l = map.diffs().listen(|_: &Vec<MapDiff<i32,i32>>| {});
+        let id = sodium_ctx.add_transaction_observer(UpdateCount { count: count.clone() });
+        cells[0].send(&100);
+        sodium_ctx.remove_transaction_observer(id);
+        l.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+    let count = *count.borrow();
+    count
+}
+
+#[test]
+fn flatten_update_cost_does_not_grow_with_size() {
+    assert_eq!(flatten_updates_for_one_send(2), flatten_updates_for_one_send(50));
+}
+
+#[test]
+fn flatten_drops_update_of_removed_cell() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let a: CellSink<i32> = sodium_ctx.new_cell_sink(1);
+        let b: CellSink<i32> = sodium_ctx.new_cell_sink(2);
+        let changes = sodium_ctx.new_stream_sink();
+        let mut initial = BTreeMap::new();
+        initial.insert("a", a.to_cell());
+        initial.insert("b", b.to_cell());
+        let map = CellMap::hold(initial, &changes).flatten();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = map.diffs().listen(move |diffs: &Vec<MapDiff<&'static str,i32>>| out.borrow_mut().push(diffs.clone()));
+        }
+        sodium_ctx.transaction(|_| {
+            a.send(&3);
+            b.send(&4);
+            changes.send(&vec![MapChange::Remove("a")]);
+        });
+        a.send(&5);
+        l.unlisten();
+        assert_eq!(
+            vec![vec![MapDiff::Remove("a", 1), MapDiff::Update("b", 2, 4)]],
+            *out.borrow()
+        );
+        assert_eq!(vec![("b", 4)], map.snapshot().sample().into_iter().collect::<Vec<_>>());
+    }
+    assert_memory_freed(sodium_ctx);
+}
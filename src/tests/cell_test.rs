@@ -1,7 +1,10 @@
 use sodium::Cell;
 use sodium::CellSink;
 use sodium::IsCell;
+use sodium::Lambda;
 use sodium::SodiumCtx;
+use sodium::testing::run_marbles;
+use lift;
 use tests::assert_memory_freed;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -123,6 +126,70 @@ fn lift_cells_in_switch_c() {
     assert_memory_freed(sodium_ctx);
 }
 
+#[test]
+fn lift_all() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    sodium_ctx.set_node_tracking(true);
+    {
+        let cells: Vec<CellSink<i32>> = (0..30).map(|i| sodium_ctx.new_cell_sink(i)).collect();
+        let all = Cell::lift_all(sodium_ctx, cells.clone());
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = all.map(|a: &Vec<i32>| a.iter().sum::<i32>()).listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        sodium_ctx.transaction(|_| {
+            cells[0].send(&100);
+            cells[29].send(&100);
+        });
+        cells[5].send(&0);
+        assert_eq!(vec![435, 606, 601], *out.borrow());
+        let graph = sodium_ctx.dump_graph();
+        let node = graph.nodes.iter().find(|node| node.desc == "Cell::lift_all_node").unwrap();
+        assert_eq!(30, node.dependencies.len());
+        let max_dep_rank = node.dependencies.iter().map(|id| graph.node(*id).unwrap().rank).max().unwrap();
+        assert_eq!(max_dep_rank + 1, node.rank);
+        assert_eq!(Vec::<i32>::new(), Cell::lift_all(sodium_ctx, Vec::<Cell<i32>>::new()).sample());
+        l.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Cell::lift_all")]
+fn lift_all_across_contexts_panics() {
+    let sodium_ctx1 = SodiumCtx::new();
+    let sodium_ctx2 = SodiumCtx::new();
+    let c = sodium_ctx2.new_cell(1);
+    Cell::lift_all(&sodium_ctx1, vec![c]);
+}
+
+#[test]
+fn lift_macro() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let a = sodium_ctx.new_cell_sink(1);
+        let a3 = a.map(|x: &i32| *x * 3);
+        let b = sodium_ctx.new_cell_sink(String::from("x"));
+        let c = lift!(|a: &i32, a3: &i32, b: &String| format!("{} {} {}", a, a3, b), a, a3, b);
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = c.listen(move |s: &String| out.borrow_mut().push(s.clone()));
+        }
+        a.send(&2);
+        b.send(&String::from("y"));
+        l.unlisten();
+        assert_eq!(vec![String::from("1 3 x"), String::from("2 6 x"), String::from("2 6 y")], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
 /*
   "should throw an error on mapCLateListen"() {
     const c = new CellSink<number>(6),
@@ -219,3 +286,29 @@ fn lift_cells_in_switch_c() {
   };
 
 }*/
+
+#[test]
+fn switch_map_c() {
+    run_marbles(|m| {
+        let sel = m.cell_sink(false, "-t--", &[('t', true)]);
+        let c1 = m.cell_sink(1, "--x-", &[('x', 3)]);
+        let c2 = m.cell_sink(10, "---y", &[('y', 20)]);
+        let out;
+        {
+            let c1 = c1.to_cell();
+            let c2 = c2.to_cell();
+            let deps = vec![c1.to_dep(), c2.to_dep()];
+            out = sel.to_cell().switch_map_c(Lambda::new(move |sel: &bool| if *sel { c2.clone() } else { c1.clone() }, deps));
+        }
+        m.expect_cell(&out, "ab-c|", &[('a', 1), ('b', 10), ('c', 20)]);
+    });
+}
+
+#[test]
+fn calm() {
+    run_marbles(|m| {
+        let values = [('1', 1), ('2', 2), ('3', 3)];
+        let c = m.cell_sink(1, "-1223-3", &values);
+        m.expect_cell(&c.calm(), "1-2-3--|", &values);
+    });
+}
@@ -0,0 +1,121 @@
+use sodium::Cell;
+use sodium::IsStream;
+use sodium::SodiumCtx;
+use sodium::Stream;
+use sodium::StreamSink;
+use sodium::gc::Finalize;
+use sodium::gc::GcDep;
+use sodium::gc::Trace;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone, Trace, Finalize)]
+struct Named<A: Clone + Trace + Finalize + 'static> {
+    s: Stream<A>,
+    #[trace(skip)]
+    #[allow(dead_code)]
+    count: Rc<RefCell<u32>>
+}
+
+#[derive(Clone, Trace, Finalize)]
+struct Tuple(StreamSink<i32>, u32);
+
+#[derive(Clone, Trace, Finalize)]
+enum Either {
+    Left(Stream<i32>),
+    Right { s: Stream<i32>, tag: String },
+    Neither
+}
+
+/// Counts how often it is finalized.
+struct Counted(Rc<RefCell<u32>>);
+
+impl Trace for Counted {
+    fn trace(&self, _f: &mut dyn FnMut(&GcDep)) {}
+}
+
+impl Finalize for Counted {
+    fn finalize(&mut self) {
+        *self.0.borrow_mut() += 1;
+    }
+}
+
+#[derive(Trace, Finalize)]
+struct Finalized {
+    a: Counted,
+    b: (u32, Counted),
+    #[trace(skip)]
+    skipped: Counted
+}
+
+#[derive(Trace, Finalize)]
+enum FinalizedEnum {
+    One(Counted),
+    Two { a: Counted, b: Counted }
+}
+
+fn trace_count<A: Trace>(a: &A) -> usize {
+    let mut n = 0;
+    a.trace(&mut |_dep: &GcDep| n += 1);
+    n
+}
+
+#[test]
+fn derive_visits_fields() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let sink: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let s = sink.to_stream();
+        assert_eq!(trace_count(&s), trace_count(&Named { s: s.clone(), count: Rc::new(RefCell::new(0)) }));
+        assert_eq!(trace_count(&sink), trace_count(&Tuple(sink.clone(), 1)));
+        assert_eq!(trace_count(&s), trace_count(&Either::Left(s.clone())));
+        assert_eq!(trace_count(&s), trace_count(&Either::Right { s: s.clone(), tag: String::from("x") }));
+        assert_eq!(0, trace_count(&Either::Neither));
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn derived_struct_through_sink() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let sink: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let ss: StreamSink<Tuple> = sodium_ctx.new_stream_sink();
+        let c = ss.hold(Tuple(sink.clone(), 0));
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = Cell::switch_s(c.map(|t: &Tuple| t.0.to_stream()))
+                .listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        sink.send(&1);
+        let sink2: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        ss.send(&Tuple(sink2.clone(), 1));
+        sink.send(&2);
+        sink2.send(&3);
+        l.unlisten();
+        assert_eq!(vec![1, 3], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn derive_finalizes_fields() {
+    let finalized = Rc::new(RefCell::new(0));
+    let mut a = Finalized {
+        a: Counted(finalized.clone()),
+        b: (1, Counted(finalized.clone())),
+        skipped: Counted(Rc::new(RefCell::new(0)))
+    };
+    a.finalize();
+    assert_eq!(2, *finalized.borrow());
+    assert_eq!(0, *a.skipped.0.borrow());
+    FinalizedEnum::One(Counted(finalized.clone())).finalize();
+    assert_eq!(3, *finalized.borrow());
+    FinalizedEnum::Two { a: Counted(finalized.clone()), b: Counted(finalized.clone()) }.finalize();
+    assert_eq!(5, *finalized.borrow());
+}
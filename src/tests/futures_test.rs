@@ -0,0 +1,104 @@
+use futures::FutureExt;
+use futures::StreamExt;
+use futures::executor::LocalPool;
+use futures::executor::block_on;
+use futures::stream;
+use futures::task::LocalSpawnExt;
+use sodium::BufferPolicy;
+use sodium::IsCell;
+use sodium::IsStream;
+use sodium::ListenerStream;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn buffered(policy: BufferPolicy) -> Vec<i32> {
+    let sodium_ctx = SodiumCtx::new();
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    let mut updates = ListenerStream::from_stream(&s, policy);
+    s.send(&1);
+    s.send(&2);
+    s.send(&3);
+    let mut out = Vec::new();
+    while let Some(Some(a)) = updates.next().now_or_never() {
+        out.push(a);
+    }
+    out
+}
+
+#[test]
+fn buffer_policies() {
+    assert_eq!(vec![1, 2, 3], buffered(BufferPolicy::Unbounded));
+    assert_eq!(vec![2, 3], buffered(BufferPolicy::DropOldest(2)));
+    assert_eq!(vec![1, 2], buffered(BufferPolicy::DropNewest(2)));
+    assert_eq!(vec![3], buffered(BufferPolicy::DropOldest(0)));
+    assert_eq!(vec![1], buffered(BufferPolicy::DropNewest(0)));
+    assert_eq!(vec![3], buffered(BufferPolicy::KeepLatest));
+}
+
+#[test]
+fn stream_wakes_task() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut pool = LocalPool::new();
+        {
+            let out = out.clone();
+            let updates = ListenerStream::from_stream(&s, BufferPolicy::Unbounded);
+            pool.spawner().spawn_local(updates.for_each(move |a| {
+                out.borrow_mut().push(a);
+                futures::future::ready(())
+            })).unwrap();
+        }
+        pool.run_until_stalled();
+        assert!(out.borrow().is_empty());
+        s.send(&1);
+        pool.run_until_stalled();
+        s.send(&2);
+        s.send(&3);
+        pool.run_until_stalled();
+        assert_eq!(vec![1, 2, 3], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn cell_updates() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let c = sodium_ctx.new_cell_sink(1);
+        let updates = ListenerStream::from_cell(&c, BufferPolicy::Unbounded);
+        c.send(&2);
+        assert_eq!(vec![1, 2], block_on(updates.take(2).collect::<Vec<_>>()));
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn sinks() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let c = s.hold(0);
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        block_on(stream::iter(vec![1, 2, 3]).map(Ok).forward(s.clone())).unwrap();
+        let cs = sodium_ctx.new_cell_sink(0);
+        block_on(stream::iter(vec![4, 5]).map(Ok).forward(cs.clone())).unwrap();
+        l.unlisten();
+        assert_eq!(vec![1, 2, 3], *out.borrow());
+        assert_eq!(3, c.sample());
+        assert_eq!(5, cs.sample());
+    }
+    assert_memory_freed(sodium_ctx);
+}
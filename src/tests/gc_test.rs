@@ -1,9 +1,12 @@
+use sodium::gc::CollectionPolicy;
 use sodium::gc::Finalize;
 use sodium::gc::Gc;
 use sodium::gc::GcCell;
 use sodium::gc::GcDep;
+use sodium::gc::NoGc;
 use sodium::gc::Trace;
 use sodium::gc::GcCtx;
+use impl_no_gc;
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -222,3 +225,121 @@ fn gc_upcast() {
         (*b).borrow_mut().inc();
     }
 }
+
+#[test]
+fn gc_std_impls() {
+    struct Plain {
+        value: i32
+    }
+    impl_no_gc!(Plain);
+    fn count<A: Trace>(a: &A) -> usize {
+        let mut n = 0;
+        a.trace(&mut |_dep: &GcDep| n += 1);
+        n
+    }
+    let mut gc_ctx = GcCtx::new();
+    let gc = gc_ctx.new_gc(Plain { value: 1 });
+    assert_eq!(1, gc.value);
+    assert_eq!(0, count(&Plain { value: 2 }));
+    assert_eq!(0, count(&(1u128, String::from("a"), Box::<str>::from("b"))));
+    assert_eq!(0, count(&NoGc::new(Rc::new(gc.clone()))));
+    assert_eq!(1, count(&GcCell::new(gc.clone())));
+    assert_eq!(2, count(&[gc.clone(), gc.clone()]));
+    assert_eq!(3, count(&(Some(Box::new(gc.clone())), vec![gc.clone(), gc.clone()])));
+    struct Finalized {
+        count: Rc<Cell<u32>>
+    }
+    impl Trace for Finalized {
+        fn trace(&self, _f: &mut dyn FnMut(&GcDep)) {}
+    }
+    impl Finalize for Finalized {
+        fn finalize(&mut self) {
+            self.count.set(self.count.get() + 1);
+        }
+    }
+    let finalized = Rc::new(Cell::new(0));
+    let new_finalized = || Finalized { count: finalized.clone() };
+    [new_finalized(), new_finalized()].finalize();
+    assert_eq!(2, finalized.get());
+    (Some(Box::new(new_finalized())), vec![new_finalized()]).finalize();
+    assert_eq!(4, finalized.get());
+}
+
+#[test]
+fn gc_collection_policy() {
+    struct A {
+        next: Cell<Option<Gc<A>>>
+    }
+    impl Trace for A {
+        fn trace(&self, f: &mut dyn FnMut(&GcDep)) {
+            let next = unsafe { &*self.next.as_ptr() };
+            next.trace(f);
+        }
+    }
+    impl Finalize for A {}
+    let mut gc_ctx = GcCtx::new();
+    gc_ctx.set_collection_policy(CollectionPolicy::Manual);
+    {
+        let a = gc_ctx.new_gc(A { next: Cell::new(None) });
+        let b = gc_ctx.new_gc(A { next: Cell::new(Some(a.clone())) });
+        a.next.set(Some(b.clone()));
+        let _c = gc_ctx.new_gc(A { next: Cell::new(None) });
+    }
+    let stats = gc_ctx.stats();
+    assert_eq!(3, stats.allocated);
+    assert_eq!(1, stats.freed);
+    assert_eq!(2, stats.live);
+    assert!(stats.candidate_roots > 0);
+    assert_eq!(0, stats.collections);
+    gc_ctx.collect_cycles();
+    let stats = gc_ctx.stats();
+    assert_eq!(0, stats.live);
+    assert_eq!(0, stats.candidate_roots);
+    assert_eq!(1, stats.collections);
+
+    gc_ctx.set_collection_policy(CollectionPolicy::EveryNRoots(2));
+    {
+        let a = gc_ctx.new_gc(A { next: Cell::new(None) });
+        a.next.set(Some(a.clone()));
+    }
+    assert_eq!(1, gc_ctx.stats().live);
+    {
+        let a = gc_ctx.new_gc(A { next: Cell::new(None) });
+        a.next.set(Some(a.clone()));
+    }
+    assert_eq!(0, gc_ctx.stats().live);
+    assert_eq!(2, gc_ctx.stats().collections);
+}
+
+#[test]
+fn gc_cycle_keeps_live_child() {
+    struct A {
+        finalized: Rc<Cell<u32>>,
+        next: Cell<Option<Gc<A>>>,
+        child: Option<Gc<A>>
+    }
+    impl Trace for A {
+        fn trace(&self, f: &mut dyn FnMut(&GcDep)) {
+            let next = unsafe { &*self.next.as_ptr() };
+            next.trace(f);
+            self.child.trace(f);
+        }
+    }
+    impl Finalize for A {
+        fn finalize(&mut self) {
+            self.finalized.set(self.finalized.get() + 1);
+        }
+    }
+    let finalized = Rc::new(Cell::new(0));
+    let mut gc_ctx = GcCtx::new();
+    let child = gc_ctx.new_gc(A { finalized: finalized.clone(), next: Cell::new(None), child: None });
+    {
+        let a = gc_ctx.new_gc(A { finalized: finalized.clone(), next: Cell::new(None), child: Some(child.clone()) });
+        let b = gc_ctx.new_gc(A { finalized: finalized.clone(), next: Cell::new(Some(a.clone())), child: Some(child.clone()) });
+        a.next.set(Some(b.clone()));
+    }
+    assert_eq!(2, finalized.get());
+    assert_eq!(1, child.strong_count());
+    drop(child);
+    assert_eq!(3, finalized.get());
+}
@@ -0,0 +1,89 @@
+use sodium::IsStream;
+use sodium::Retention;
+use sodium::SodiumCtx;
+use sodium::StreamLoop;
+use sodium::StreamSink;
+use sodium::gc::CollectionPolicy;
+use tests::assert_memory_freed;
+
+#[test]
+fn dump_graph() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    sodium_ctx.set_node_tracking(true);
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let s2 = s.map(|a: &i32| *a + 1);
+        let l = s2.listen(|_: &i32| {});
+        let graph = sodium_ctx.dump_graph();
+        let sink_id = graph.nodes.iter().find(|node| node.desc == "StreamSink::new_node").unwrap().id;
+        let map_node = graph.nodes.iter().find(|node| node.dependencies == vec![sink_id]).unwrap();
+        let listen_node = graph.nodes.iter().find(|node| node.dependencies == vec![map_node.id]).unwrap();
+        assert!(map_node.rank > graph.node(sink_id).unwrap().rank);
+        assert!(listen_node.kept_alive);
+        assert!(!map_node.kept_alive);
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph sodium {"));
+        assert!(dot.contains(&format!("n{} -> n{};", sink_id, map_node.id)));
+        assert!(dot.contains(&format!("n{} -> n{};", map_node.id, listen_node.id)));
+        #[cfg(feature = "serde")]
+        {
+            let json = ::serde_json::to_value(&graph).unwrap();
+            assert_eq!(graph.nodes.len(), json["nodes"].as_array().unwrap().len());
+            assert_eq!(json!(true), json["nodes"].as_array().unwrap().iter().find(|node| node["id"] == json!(listen_node.id)).unwrap()["kept_alive"]);
+        }
+        l.unlisten();
+    }
+    assert!(sodium_ctx.dump_graph().nodes.is_empty());
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn leak_report() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    sodium_ctx.set_node_tracking(true);
+    sodium_ctx.gc_ctx().set_collection_policy(CollectionPolicy::Manual);
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let l = s.map(|a: &i32| *a + 1).listen(|_: &i32| {});
+        let c = sodium_ctx.new_cell(1);
+        sodium_ctx.transaction(|sodium_ctx| {
+            let sl: StreamLoop<i32> = sodium_ctx.new_stream_loop();
+            sl.loop_(sl.map(|a: &i32| *a + 1));
+        });
+        let report = sodium_ctx.leak_report();
+        assert_eq!(6, report.nodes.len());
+        let retention = |desc: &str| report.nodes.iter().find(|node| node.desc == desc).unwrap().retention.clone();
+        match retention("Stream::listen_node") {
+            Retention::Listener { path } => assert_eq!(1, path.len()),
+            _ => panic!("expected Retention::Listener")
+        }
+        assert_eq!(Retention::Held { path: vec![String::from("3 Cell::new_node")] }, retention("Cell::new_node"));
+        assert_eq!(Retention::Cycle, retention("Stream::new_node"));
+        assert!(report.to_string().contains("1 Stream::map_node: kept alive by a listener: 2 Stream::listen_node -> 1 Stream::map_node"));
+        drop(s);
+        drop(c);
+        l.unlisten();
+    }
+    sodium_ctx.gc_ctx().collect_cycles();
+    assert!(sodium_ctx.leak_report().is_empty());
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn nodes_not_tracked_by_default() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let l = s.map(|a: &i32| *a + 1).listen(|_: &i32| {});
+        assert!(sodium_ctx.dump_graph().nodes.is_empty());
+        sodium_ctx.set_node_tracking(true);
+        let l2 = s.listen(|_: &i32| {});
+        assert_eq!(1, sodium_ctx.dump_graph().nodes.len());
+        l.unlisten();
+        l2.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+}
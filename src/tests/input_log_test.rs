@@ -0,0 +1,77 @@
+use sodium::CellSink;
+use sodium::InputLog;
+use sodium::InputRecorder;
+use sodium::InputReplayer;
+use sodium::IsStream;
+use sodium::Listener;
+use sodium::ReplayError;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn build(sodium_ctx: &SodiumCtx, out: &Rc<RefCell<Vec<String>>>) -> (StreamSink<i32>, CellSink<String>, Listener) {
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    let c: CellSink<String> = sodium_ctx.new_cell_sink(String::from("a"));
+    let out = out.clone();
+    let s2 = s.clone();
+    let sodium_ctx2 = sodium_ctx.clone();
+    let l = s.snapshot2(&c, |a: &i32, b: &String| format!("{}{}", b, a)).listen(move |a: &String| {
+        out.borrow_mut().push(a.clone());
+        // Sends made from post are a consequence of the inputs, not inputs.
+        if a == "b1" {
+            let s2 = s2.clone();
+            sodium_ctx2.post(move || s2.send(&100));
+        }
+    });
+    (s, c, l)
+}
+
+#[test]
+fn record_and_replay() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let log;
+    {
+        let (s, c, l) = build(sodium_ctx, &out);
+        let recorder = InputRecorder::new();
+        recorder.stream_sink("s", &s);
+        recorder.cell_sink("c", &c);
+        s.send(&1);
+        c.send(&String::from("b"));
+        sodium_ctx.transaction(|_| {
+            s.send(&1);
+            c.send(&String::from("c"));
+        });
+        s.send(&2);
+        l.unlisten();
+        log = recorder.log();
+    }
+    assert_memory_freed(sodium_ctx);
+    assert_eq!(vec!["a1", "b1", "c100", "c2"], *out.borrow());
+    assert_eq!(4, log.transactions.len());
+    assert_eq!(2, log.transactions[2].len());
+    let json = serde_json::to_string(&log).unwrap();
+    let log: InputLog = serde_json::from_str(&json).unwrap();
+
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let out2 = Rc::new(RefCell::new(Vec::new()));
+    {
+        let (s, c, l) = build(sodium_ctx, &out2);
+        let mut replayer = InputReplayer::new(sodium_ctx);
+        replayer.stream_sink("s", &s);
+        replayer.cell_sink("c", &c);
+        replayer.replay(&log).unwrap();
+        let unknown: InputLog = serde_json::from_value(json!({ "transactions": [[{ "sink": "x", "value": 1 }]] })).unwrap();
+        match replayer.replay(&unknown) {
+            Err(ReplayError::UnknownSink(sink)) => assert_eq!("x", sink),
+            _ => panic!("expected ReplayError::UnknownSink")
+        }
+        l.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+    assert_eq!(*out.borrow(), *out2.borrow());
+}
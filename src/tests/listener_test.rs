@@ -0,0 +1,77 @@
+use sodium::IsStream;
+use sodium::ListenerSet;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn listener_guard() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let out = Rc::new(RefCell::new(Vec::new()));
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        {
+            let out = out.clone();
+            let _guard = s.listen(move |a: &i32| out.borrow_mut().push(*a)).guard();
+            s.send(&1);
+        }
+        s.send(&2);
+        let guard;
+        {
+            let out = out.clone();
+            guard = s.map(|a: &i32| *a * 10).listen(move |a: &i32| out.borrow_mut().push(*a)).guard();
+        }
+        s.send(&3);
+        let l = guard.into_listener();
+        s.send(&4);
+        l.unlisten();
+    }
+    assert_eq!(vec![1, 30, 40], *out.borrow());
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn listener_detach() {
+    let sodium_ctx = SodiumCtx::new();
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    {
+        let out = out.clone();
+        s.listen(move |a: &i32| out.borrow_mut().push(*a)).detach();
+    }
+    s.send(&1);
+    assert_eq!(vec![1], *out.borrow());
+    assert!(sodium_ctx.node_count() > 0);
+}
+
+#[test]
+fn listener_set() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let out = Rc::new(RefCell::new(Vec::new()));
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let mut listeners = ListenerSet::new();
+        for i in 0..3 {
+            let out = out.clone();
+            listeners.add(s.listen(move |a: &i32| out.borrow_mut().push(*a + i)));
+        }
+        assert_eq!(3, listeners.len());
+        s.send(&10);
+        listeners.unlisten_all();
+        assert!(listeners.is_empty());
+        s.send(&20);
+        {
+            let out = out.clone();
+            listeners.add(s.listen(move |a: &i32| out.borrow_mut().push(*a)));
+        }
+        s.send(&30);
+        drop(listeners);
+        s.send(&40);
+    }
+    assert_eq!(vec![10, 11, 12, 30], *out.borrow());
+    assert_memory_freed(sodium_ctx);
+}
@@ -1,7 +1,25 @@
 pub use self::memory_check::assert_memory_freed;
 
+mod callback_send_test;
 mod cell_test;
 mod cell_loop_test;
+mod cell_map_test;
+#[cfg(feature = "derive")]
+mod derive_test;
+#[cfg(feature = "futures")]
+mod futures_test;
 mod gc_test;
+mod graph_dump_test;
+#[cfg(feature = "record")]
+mod input_log_test;
+mod listener_test;
 mod memory_check;
+mod panic_test;
+#[cfg(feature = "persist")]
+mod persist_test;
+mod sink_sender_test;
+mod sodium_thread_test;
 mod stream_test;
+mod testing_test;
+mod timer_system_test;
+mod transaction_observer_test;
@@ -0,0 +1,63 @@
+use sodium::IsStream;
+use sodium::SodiumCtx;
+use sodium::SodiumError;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+#[test]
+fn try_transaction_recovers_from_map_panic() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let c = s.hold(0);
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.map(|a: &i32| if *a == 2 { panic!("boom") } else { *a * 10 })
+                .listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        s.send(&1);
+        let result = sodium_ctx.try_transaction(|_| s.send(&2));
+        assert_eq!(Err(SodiumError::Panicked(String::from("boom"))), result);
+        assert_eq!(Ok(7), sodium_ctx.try_transaction(|_| 7));
+        s.send(&3);
+        l.unlisten();
+        assert_eq!(vec![10, 30], *out.borrow());
+        assert_eq!(3, c.sample());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn listener_panic_leaves_ctx_usable() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.listen(move |a: &i32| {
+                if *a == 2 {
+                    panic!("boom");
+                }
+                out.borrow_mut().push(*a);
+            });
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| s.send(&2)));
+        assert!(result.is_err());
+        // A leftover callback depth would make this send panic.
+        s.send(&3);
+        sodium_ctx.transaction(|_| s.send(&4));
+        l.unlisten();
+        assert_eq!(vec![3, 4], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
@@ -0,0 +1,48 @@
+use sodium::CellState;
+use sodium::IsCell;
+use sodium::IsStream;
+use sodium::PersistError;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+
+#[test]
+fn save_and_load_state() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let state;
+    {
+        let name = sodium_ctx.new_persistent_cell_sink("name", String::from("a")).unwrap();
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let total = s.accum_lazy(sodium_ctx.restored_lazy("total", sodium_ctx.new_lazy(|| 0)).unwrap(), |a: &i32, b: &i32| *a + *b);
+        sodium_ctx.persist_cell("total", &total);
+        name.send(&String::from("b"));
+        s.send(&3);
+        s.send(&4);
+        state = sodium_ctx.save_state().unwrap();
+    }
+    assert_memory_freed(sodium_ctx);
+    assert_eq!(CellState::default(), sodium_ctx.save_state().unwrap());
+    let json = serde_json::to_string(&state).unwrap();
+    let state: CellState = serde_json::from_str(&json).unwrap();
+    assert_eq!(json!("b"), state.cells["name"]);
+    assert_eq!(json!(7), state.cells["total"]);
+
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    sodium_ctx.load_state(state);
+    {
+        let name = sodium_ctx.new_persistent_cell_sink("name", String::from("a")).unwrap();
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let total = s.accum_lazy(sodium_ctx.restored_lazy("total", sodium_ctx.new_lazy(|| 0)).unwrap(), |a: &i32, b: &i32| *a + *b);
+        assert_eq!("b", name.sample());
+        assert_eq!(7, total.sample());
+        s.send(&1);
+        assert_eq!(8, total.sample());
+        match sodium_ctx.new_persistent_cell_sink("total", String::from("a")) {
+            Err(PersistError::Decode { key, .. }) => assert_eq!("total", key),
+            _ => panic!("expected PersistError::Decode")
+        }
+    }
+    assert_memory_freed(sodium_ctx);
+}
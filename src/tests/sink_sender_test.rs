@@ -0,0 +1,99 @@
+use sodium::IsCell;
+use sodium::IsStream;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+#[test]
+fn stream_sink_sender() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        let sender = s.sender();
+        thread::spawn(move || {
+            sender.send(1);
+            sender.send(2);
+        }).join().unwrap();
+        assert!(out.borrow().is_empty());
+        assert_eq!(2, sodium_ctx.run_pending());
+        assert_eq!(0, sodium_ctx.run_pending());
+        l.unlisten();
+        assert_eq!(vec![1, 2], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn cell_sink_sender() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let c = sodium_ctx.new_cell_sink(0);
+        let notified = Arc::new(AtomicUsize::new(0));
+        {
+            let notified = notified.clone();
+            sodium_ctx.set_pending_notifier(move || { notified.fetch_add(1, Ordering::SeqCst); });
+        }
+        let sender = c.sender();
+        thread::spawn(move || sender.send(3)).join().unwrap();
+        assert_eq!(0, c.sample());
+        sodium_ctx.run_pending();
+        assert_eq!(3, c.sample());
+        assert_eq!(2, notified.load(Ordering::SeqCst));
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn sender_survives_panicking_send() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.listen(move |a: &i32| {
+                if *a == 1 {
+                    panic!("listener failed");
+                }
+                out.borrow_mut().push(*a);
+            });
+        }
+        let sender = s.sender();
+        sender.send(1);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| sodium_ctx.run_pending())).is_err());
+        sender.send(2);
+        drop(sender);
+        assert_eq!(1, sodium_ctx.run_pending());
+        l.unlisten();
+        assert_eq!(vec![2], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+#[should_panic]
+fn run_pending_in_callback() {
+    let sodium_ctx = SodiumCtx::new();
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    let sodium_ctx2 = sodium_ctx.clone();
+    let _l = s.listen(move |_a: &i32| { sodium_ctx2.run_pending(); });
+    s.send(&1);
+}
@@ -0,0 +1,70 @@
+use sodium::IsStream;
+use sodium::SodiumThread;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+#[test]
+fn send_from_other_threads() {
+    let sodium_thread = SodiumThread::spawn();
+    let handle = sodium_thread.handle();
+    let s = handle.new_stream_sink::<i32>();
+    let out = Arc::new(Mutex::new(Vec::new()));
+    {
+        let s = s.clone();
+        let out = out.clone();
+        handle.transaction_sync(move |_sodium_ctx| {
+            let _ = s.to_stream()
+                .accum(0, |a: &i32, total: &i32| *a + *total)
+                .listen(move |total: &i32| out.lock().unwrap().push(*total));
+        });
+    }
+    let threads: Vec<_> = (0..4).map(|_| {
+        let s = s.clone();
+        thread::spawn(move || {
+            for _ in 0..25 {
+                s.send(1);
+            }
+        })
+    }).collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    handle.transaction_sync(|_sodium_ctx| ());
+    let out = out.lock().unwrap();
+    assert_eq!(101, out.len());
+    assert_eq!(Some(&100), out.last());
+}
+
+#[test]
+fn cell_sink_released_with_last_handle() {
+    let sodium_thread = SodiumThread::spawn();
+    let handle = sodium_thread.handle();
+    {
+        let c = handle.new_cell_sink(1);
+        {
+            let c = c.clone();
+            thread::spawn(move || c.send(5)).join().unwrap();
+        }
+        {
+            let c = c.clone();
+            handle.transaction(move |_sodium_ctx| {
+                let sink = c.to_cell_sink();
+                sink.send(&6);
+                sink.send(&7);
+            });
+        }
+        let c2 = c.clone();
+        assert_eq!(7, handle.transaction_sync(move |_sodium_ctx| c2.to_cell().sample()));
+    }
+    handle.transaction_sync(|_sodium_ctx| ());
+    assert_eq!(0, handle.transaction_sync(|sodium_ctx| sodium_ctx.node_count()));
+}
+
+#[test]
+#[should_panic]
+fn sink_not_local_outside_sodium_thread() {
+    let sodium_thread = SodiumThread::spawn();
+    let s = sodium_thread.handle().new_stream_sink::<i32>();
+    s.to_stream_sink();
+}
@@ -10,9 +10,11 @@ use sodium::SodiumCtx;
 use sodium::Stream;
 use sodium::StreamLoop;
 use sodium::StreamSink;
+use sodium::gc::CollectionPolicy;
 use sodium::gc::Finalize;
 use sodium::gc::GcDep;
 use sodium::gc::Trace;
+use sodium::testing::run_marbles;
 use tests::assert_memory_freed;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -345,6 +347,63 @@ fn gate() {
     assert_memory_freed(sodium_ctx);
 }
 
+#[test]
+fn gate_on_cell_in_cycle_is_freed() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = sodium_ctx.transaction(|sodium_ctx| {
+                let open: CellLoop<bool> = sodium_ctx.new_cell_loop();
+                let gated = s.gate(&open);
+                open.loop_(gated.map(|_: &i32| false).hold(true));
+                gated.listen(move |a: &i32| out.borrow_mut().push(*a))
+            });
+        }
+        s.send(&1);
+        s.send(&2);
+        l.unlisten();
+        assert_eq!(vec![1], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn snapshot_all() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<()> = sodium_ctx.new_stream_sink();
+        let cells: Vec<CellSink<u32>> = (0..10).map(|i| sodium_ctx.new_cell_sink(i)).collect();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.snapshot_all(cells.clone()).listen(move |a: &Vec<u32>| out.borrow_mut().push(a.clone()));
+        }
+        s.send(&());
+        sodium_ctx.transaction(|_| {
+            cells[3].send(&30);
+            s.send(&());
+        });
+        s.send(&());
+        l.unlisten();
+        assert_eq!(
+            vec![
+                vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+                vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+                vec![0, 1, 2, 30, 4, 5, 6, 7, 8, 9]
+            ],
+            *out.borrow()
+        );
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
 #[test]
 fn collect() {
     let mut sodium_ctx = SodiumCtx::new();
@@ -403,6 +462,51 @@ fn accum() {
     assert_memory_freed(sodium_ctx);
 }
 
+#[test]
+fn accum_sample() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let ea = sodium_ctx.new_stream_sink();
+        let ea2 = ea.map(|a: &u32| *a);
+        let sum = ea2.accum(100, |a:&u32, s:&u32| *a + *s);
+        let sum2 = ea2.accum(sum.sample(), |a:&u32, s:&u32| *a + *s);
+        let l = sum2.listen(|_: &u32| {});
+        ea.send(&5);
+        assert_eq!(105, sum.sample());
+        assert_eq!(105, sum2.sample());
+        l.unlisten();
+        drop(ea2);
+        drop(sum);
+        assert_eq!(105, sum2.sample());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn snapshot_sees_value_from_before_the_transaction() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let c = s.hold(0);
+        // Held without a listener, so the snapshots are only evaluated when
+        // sampled, after the transaction has updated c.
+        let h1 = s.snapshot(&c).hold(-1);
+        let h2 = s.snapshot2(&c, |a: &i32, b: &i32| *a * 10 + *b).hold(-1);
+        let h3 = s.snapshot3(&c, &c, |a: &i32, b: &i32, c: &i32| *a * 100 + *b * 10 + *c).hold(-1);
+        s.send(&5);
+        assert_eq!(0, h1.sample());
+        assert_eq!(50, h2.sample());
+        assert_eq!(500, h3.sample());
+        s.send(&6);
+        assert_eq!(5, h1.sample());
+        assert_eq!(65, h2.sample());
+        assert_eq!(655, h3.sample());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
 #[test]
 fn once() {
     let mut sodium_ctx = SodiumCtx::new();
@@ -752,3 +856,144 @@ fn loop_cell() {
     }
     assert_memory_freed(sodium_ctx);
 }
+
+#[test]
+fn switch_map() {
+    run_marbles(|m| {
+        let sel = m.stream_sink("a--b--", &[('a', 0), ('b', 1)]);
+        let x = m.stream_sink("1-234-", &[('1', 1), ('2', 2), ('3', 3), ('4', 4)]);
+        let y = m.stream_sink("56-789", &[('5', 5), ('6', 6), ('7', 7), ('8', 8), ('9', 9)]);
+        let out;
+        {
+            let x = x.to_stream();
+            let y = y.to_stream();
+            let deps = vec![x.to_dep(), y.to_dep()];
+            out = sel.switch_map(Lambda::new(move |sel: &i32| if *sel == 0 { x.clone() } else { y.clone() }, deps));
+        }
+        let values: Vec<(char,i32)> = (1..10).map(|n| (::std::char::from_digit(n, 10).unwrap(), n as i32)).collect();
+        m.expect_stream(&out, "--2389|", &values);
+    });
+}
+
+#[test]
+fn exhaust_map() {
+    run_marbles(|m| {
+        let req = m.stream_sink("1-2---3-", &[('1', 1), ('2', 2), ('3', 3)]);
+        let resp = m.stream_sink("-5-6x--7", &[('5', Some(5)), ('6', Some(6)), ('x', None), ('7', Some(7))]);
+        let out;
+        {
+            let resp = resp.to_stream();
+            let deps = vec![resp.to_dep()];
+            out = req.exhaust_map(Lambda::new(move |n: &i32| { let n = *n; resp.map(move |r: &Option<i32>| r.map(|r| n * 100 + r)) }, deps));
+        }
+        m.expect_stream(&out, "-a-b---c|", &[('a', 105), ('b', 106), ('c', 307)]);
+    });
+}
+
+#[test]
+fn merge_all() {
+    run_marbles(|m| {
+        let a = m.stream_sink("1-2-34", &[('1', 1), ('2', 2), ('3', 3), ('4', 4)]).to_stream();
+        let b = m.stream_sink("-5-67-", &[('5', 5), ('6', 6), ('7', 7)]).to_stream();
+        let streams = m.cell_sink(vec![a.clone()], "--x-y-", &[('x', vec![a.clone(), b.clone()]), ('y', vec![b.clone()])]);
+        let out = Stream::merge_all(&streams, |l: &i32, r: &i32| *l + *r);
+        m.expect_stream(&out, "1-26t-|", &[('1', 1), ('2', 2), ('6', 6), ('t', 10)]);
+    });
+}
+
+#[test]
+fn merge_all_switch_to_higher_rank() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let deep = s.map(|a: &i32| *a + 1).map(|a: &i32| *a + 2).map(|a: &i32| *a + 3).map(|a: &i32| *a + 4);
+        let streams = sodium_ctx.new_cell_sink(vec![s.to_stream()]);
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = Stream::merge_all(&streams, |_l: &i32, r: &i32| *r)
+                .listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        streams.send(&vec![s.to_stream(), deep]);
+        s.send(&1);
+        assert_eq!(vec![11], *out.borrow());
+        l.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn calm() {
+    run_marbles(|m| {
+        let values = [('1', 1), ('2', 2), ('3', 3)];
+        let s = m.stream_sink("1122131", &values);
+        m.expect_stream(&s.calm(), "1-2-131|", &values);
+    });
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Stream::merge")]
+fn merge_across_contexts_panics() {
+    let sodium_ctx1 = SodiumCtx::new();
+    let sodium_ctx2 = SodiumCtx::new();
+    let s1: StreamSink<i32> = sodium_ctx1.new_stream_sink();
+    let s2: StreamSink<i32> = sodium_ctx2.new_stream_sink();
+    s1.or_else(&s2);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Stream::snapshot")]
+fn snapshot_across_contexts_panics() {
+    let sodium_ctx1 = SodiumCtx::new();
+    let sodium_ctx2 = SodiumCtx::new();
+    assert_ne!(sodium_ctx1.id(), sodium_ctx2.id());
+    let s: StreamSink<i32> = sodium_ctx1.new_stream_sink();
+    let c = sodium_ctx2.new_cell(1);
+    s.snapshot(&c);
+}
+
+#[test]
+fn collect_at_end_of_transaction() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    sodium_ctx.gc_ctx().set_collection_policy(CollectionPolicy::EndOfTransaction);
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    let out = Rc::new(RefCell::new(Vec::new()));
+    {
+        let out = out.clone();
+        let l = s.map(|a: &i32| *a + 1).listen(move |a: &i32| out.borrow_mut().push(*a));
+        s.send(&1);
+        l.unlisten();
+    }
+    let collections = sodium_ctx.gc_ctx().stats().collections;
+    s.send(&2);
+    assert_eq!(vec![2], *out.borrow());
+    assert!(sodium_ctx.gc_ctx().stats().collections > collections);
+    drop(s);
+    sodium_ctx.gc_ctx().collect_cycles();
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn collection_deferred_during_transaction() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    let gc_ctx = sodium_ctx.gc_ctx();
+    let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+    let collections = gc_ctx.stats().collections;
+    sodium_ctx.transaction(|_| {
+        for _ in 0..10 {
+            let l = s.map(|a: &i32| *a + 1).filter(|a: &i32| *a > 0).listen(|_: &i32| {});
+            l.unlisten();
+        }
+        assert_eq!(collections, gc_ctx.stats().collections);
+        assert!(gc_ctx.stats().candidate_roots > 0);
+    });
+    assert_eq!(collections + 1, gc_ctx.stats().collections);
+    drop(s);
+    assert_memory_freed(sodium_ctx);
+}
@@ -0,0 +1,36 @@
+use sodium::IsStream;
+use sodium::testing::run_marbles;
+
+#[test]
+fn merge_and_hold() {
+    run_marbles(|m| {
+        let a = m.stream_sink("a-b---(c)|", &[('a', 1), ('b', 2), ('c', 3)]);
+        let b = m.stream_sink("--x-y-z  |", &[('x', 10), ('y', 20), ('z', 30)]);
+        let merged = a.or_else(&b);
+        m.expect_stream(&merged, "a-b-y-c|", &[('a', 1), ('b', 2), ('c', 3), ('y', 20)]);
+        let held = merged.hold(0);
+        m.expect_cell(&held, "(0a)-b-y-c|", &[('0', 0), ('a', 1), ('b', 2), ('c', 3), ('y', 20)]);
+    });
+}
+
+#[test]
+fn simultaneous_events() {
+    run_marbles(|m| {
+        let coalesced = m.sodium_ctx().new_stream_sink_with_coalescer(|l: &i32, r: &i32| *l + *r);
+        m.send_stream(&coalesced, "a-(ab)-", &[('a', 1), ('b', 2)]);
+        let c = m.cell_sink(100, "--x", &[('x', 200)]);
+        // The snapshot sees the cell's value from before the transaction.
+        let sum = coalesced.snapshot2(&c, |a: &i32, c: &i32| *a + *c);
+        m.expect_stream(&sum, "a-b|", &[('a', 101), ('b', 103)]);
+    });
+}
+
+#[test]
+#[should_panic(expected = "actual: a-c")]
+fn reports_mismatch() {
+    run_marbles(|m| {
+        let s = m.stream_sink("a-b", &[('a', 1), ('b', 2)]);
+        let out = s.map(|a: &i32| *a * 10);
+        m.expect_stream(&out, "a-b", &[('a', 10), ('b', 21), ('c', 20)]);
+    });
+}
@@ -0,0 +1,207 @@
+use sodium::CellSink;
+use sodium::IsStream;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use sodium::TimerSystem;
+use sodium::VirtualClock;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[test]
+fn time() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = timer_system.time().listen(
+                move |t: &Duration|
+                    out.borrow_mut().push(t.as_millis())
+            );
+        }
+        clock.advance(Duration::from_millis(100));
+        timer_system.run_timers();
+        timer_system.run_timers();
+        clock.advance(Duration::from_millis(50));
+        timer_system.run_timers();
+        l.unlisten();
+        assert_eq!(vec![0, 100, 150], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn at() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        let alarm: CellSink<Option<Duration>> = sodium_ctx.new_cell_sink(Some(Duration::from_millis(100)));
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = timer_system.at(&alarm).listen(
+                move |t: &Duration|
+                    out.borrow_mut().push(t.as_millis())
+            );
+        }
+        clock.advance(Duration::from_millis(90));
+        timer_system.run_timers();
+        clock.advance(Duration::from_millis(20));
+        timer_system.run_timers();
+        assert_eq!(Duration::from_millis(110), timer_system.time().sample());
+        alarm.send(&Some(Duration::from_millis(200)));
+        alarm.send(&Some(Duration::from_millis(300)));
+        clock.advance(Duration::from_millis(100));
+        timer_system.run_timers();
+        alarm.send(&None);
+        clock.advance(Duration::from_millis(100));
+        timer_system.run_timers();
+        assert_eq!(None, timer_system.next_alarm_time());
+        l.unlisten();
+        assert_eq!(vec![100], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn at_cancelled_when_collected() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        {
+            let s = timer_system.at(sodium_ctx.new_cell(Some(Duration::from_millis(100))));
+            assert_eq!(Some(Duration::from_millis(100)), timer_system.next_alarm_time());
+            let _ = s;
+        }
+        assert_eq!(None, timer_system.next_alarm_time());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+fn advance_to(clock: &VirtualClock, timer_system: &TimerSystem, millis: u64) {
+    clock.set_now(Duration::from_millis(millis));
+    timer_system.run_timers();
+}
+
+#[test]
+fn debounce() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.debounce(&timer_system, Duration::from_millis(100)).listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        s.send(&1);
+        advance_to(&clock, &timer_system, 50);
+        s.send(&2);
+        advance_to(&clock, &timer_system, 100);
+        assert!(out.borrow().is_empty());
+        advance_to(&clock, &timer_system, 160);
+        s.send(&3);
+        advance_to(&clock, &timer_system, 300);
+        advance_to(&clock, &timer_system, 400);
+        l.unlisten();
+        assert_eq!(vec![2, 3], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn throttle() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.throttle(&timer_system, Duration::from_millis(100)).listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        s.send(&1);
+        advance_to(&clock, &timer_system, 50);
+        s.send(&2);
+        advance_to(&clock, &timer_system, 100);
+        s.send(&3);
+        s.send(&4);
+        l.unlisten();
+        assert_eq!(vec![1, 3], *out.borrow());
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn delay() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let l;
+        {
+            let out = out.clone();
+            l = s.delay(&timer_system, Duration::from_millis(100)).listen(move |a: &i32| out.borrow_mut().push(*a));
+        }
+        s.send(&1);
+        advance_to(&clock, &timer_system, 30);
+        s.send(&2);
+        advance_to(&clock, &timer_system, 110);
+        assert_eq!(vec![1], *out.borrow());
+        advance_to(&clock, &timer_system, 500);
+        assert_eq!(vec![1, 2], *out.borrow());
+        assert_eq!(None, timer_system.next_alarm_time());
+        l.unlisten();
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn timeout_and_periodic() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let clock = VirtualClock::new();
+        let timer_system = sodium_ctx.new_timer_system(clock.clone());
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let timeouts = Rc::new(RefCell::new(Vec::new()));
+        let ticks = Rc::new(RefCell::new(Vec::new()));
+        let l1;
+        let l2;
+        {
+            let timeouts = timeouts.clone();
+            l1 = s.timeout(&timer_system, Duration::from_millis(100)).listen(move |t: &Duration| timeouts.borrow_mut().push(t.as_millis()));
+            let ticks = ticks.clone();
+            l2 = timer_system.periodic(Duration::from_millis(100)).listen(move |t: &Duration| ticks.borrow_mut().push(t.as_millis()));
+        }
+        advance_to(&clock, &timer_system, 50);
+        s.send(&1);
+        advance_to(&clock, &timer_system, 350);
+        l1.unlisten();
+        l2.unlisten();
+        assert_eq!(vec![150], *timeouts.borrow());
+        assert_eq!(vec![100, 200, 300], *ticks.borrow());
+        assert_eq!(None, timer_system.next_alarm_time());
+    }
+    assert_memory_freed(sodium_ctx);
+}
@@ -0,0 +1,82 @@
+use sodium::HookKind;
+use sodium::IsStream;
+use sodium::SodiumCtx;
+use sodium::StreamSink;
+use sodium::TransactionObserver;
+use sodium::TransactionRecorder;
+use tests::assert_memory_freed;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct EventLog {
+    events: Rc<RefCell<Vec<String>>>
+}
+
+impl TransactionObserver for EventLog {
+    fn transaction_start(&mut self) {
+        self.events.borrow_mut().push(String::from("start"));
+    }
+
+    fn transaction_end(&mut self) {
+        self.events.borrow_mut().push(String::from("end"));
+    }
+
+    fn node_update_start(&mut self, _id: u32, _desc: &str, rank: u32) {
+        self.events.borrow_mut().push(format!("update {}", rank));
+    }
+
+    fn hook_start(&mut self, kind: HookKind) {
+        self.events.borrow_mut().push(format!("{:?}", kind));
+    }
+}
+
+#[test]
+fn observer_sees_rank_order() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let l = s.map(|a: &i32| *a + 1).listen(|_: &i32| {});
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let id = sodium_ctx.add_transaction_observer(EventLog { events: events.clone() });
+        sodium_ctx.transaction(|sodium_ctx| {
+            sodium_ctx.post(|| {});
+            s.send(&1);
+        });
+        sodium_ctx.remove_transaction_observer(id);
+        s.send(&2);
+        l.unlisten();
+        let events = events.borrow();
+        assert_eq!("start", events[0]);
+        assert_eq!("end", events[events.len() - 1]);
+        assert_eq!("Post", events[events.len() - 2]);
+        let ranks: Vec<u32> = events.iter().filter(|e| e.starts_with("update ")).map(|e| e[7..].parse().unwrap()).collect();
+        assert_eq!(3, ranks.len());
+        assert!(ranks.windows(2).all(|w| w[0] <= w[1]));
+    }
+    assert_memory_freed(sodium_ctx);
+}
+
+#[test]
+fn recorder_reports() {
+    let mut sodium_ctx = SodiumCtx::new();
+    let sodium_ctx = &mut sodium_ctx;
+    {
+        let recorder = TransactionRecorder::new();
+        sodium_ctx.add_transaction_observer(recorder.clone());
+        let s: StreamSink<i32> = sodium_ctx.new_stream_sink();
+        let l = s.map(|a: &i32| *a + 1).listen(|_: &i32| {});
+        recorder.clear();
+        s.send(&1);
+        s.send(&2);
+        l.unlisten();
+        let reports = recorder.reports();
+        assert_eq!(2, reports.len());
+        assert_eq!(3, reports[0].marked_dirty.len());
+        assert_eq!(3, reports[0].node_updates.len());
+        assert!(reports[0].node_updates[0].changed);
+        assert_eq!("StreamSink::new_node", reports[0].node_updates[0].desc);
+        assert!(reports[0].to_string().starts_with("transaction "));
+    }
+    assert_memory_freed(sodium_ctx);
+}